@@ -0,0 +1,182 @@
+// HTTP-date (RFC 9110 section 5.6.7)
+//
+// Sun, 06 Nov 1994 08:49:37 GMT   // IMF-fixdate, the only format we send
+// Sunday, 06-Nov-94 08:49:37 GMT  // obsolete RFC 850 format
+// Sun Nov  6 08:49:37 1994        // obsolete asctime format
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats `time` as IMF-fixdate, times before the epoch are clamped to it.
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+
+    let days = secs / 86400;
+    let rem = secs % 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Parses a date in any of the three formats recipients have to accept,
+/// `None` if it is invalid or before the epoch.
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let parts: Vec<_> = value.split_ascii_whitespace().collect();
+
+    let (day, month, year, time) = match parts[..] {
+        [_, day, month, year, time, "GMT"] => (day, month, year.parse().ok()?, time),
+        [_, date, time, "GMT"] => {
+            let mut date = date.split('-');
+            let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+            if date.next().is_some() || year.len() != 2 {
+                return None;
+            }
+
+            // two digit years are put into the century that makes them the
+            // closest to now, close enough for the dates still around
+            let year: i64 = year.parse().ok()?;
+            let year = if year < 70 { 2000 + year } else { 1900 + year };
+            (day, month, year, time)
+        }
+        [_, month, day, time, year] => (day, month, year.parse().ok()?, time),
+        _ => return None,
+    };
+
+    let number = |s: &str, len: std::ops::RangeInclusive<usize>| {
+        if !len.contains(&s.len()) || !s.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        s.parse::<i64>().ok()
+    };
+
+    let day = number(day, 1..=2)?;
+    let month = MONTHS.iter().position(|&m| m == month)? as i64 + 1;
+
+    let mut time = time.split(':');
+    let (hour, minute, second) = (
+        number(time.next()?, 2..=2)?,
+        number(time.next()?, 2..=2)?,
+        number(time.next()?, 2..=2)?,
+    );
+    if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // the year is only bounded here, the calculations below would overflow
+    // for the largest ones
+    if !(1970..=9999).contains(&year) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month as u32, day as u32);
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Converts a date of the proleptic Gregorian calendar into days since the
+/// epoch, the inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+/// Converts days since the epoch into a (year, month, day) date of the
+/// proleptic Gregorian calendar, see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn format() {
+        let all = [
+            (0, "Thu, 01 Jan 1970 00:00:00 GMT"),
+            (784111777, "Sun, 06 Nov 1994 08:49:37 GMT"),
+            (951782400, "Tue, 29 Feb 2000 00:00:00 GMT"),
+            (4102444799, "Thu, 31 Dec 2099 23:59:59 GMT"),
+        ];
+
+        for (secs, exp) in all {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            assert_eq!(exp, format_http_date(time));
+            assert_eq!(Some(time), parse_http_date(exp));
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let exp = Some(UNIX_EPOCH + Duration::from_secs(784111777));
+
+        assert_eq!(exp, parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(exp, parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"));
+        assert_eq!(exp, parse_http_date("Sun Nov  6 08:49:37 1994"));
+        assert!(parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT").is_some());
+        assert!(parse_http_date("Fri, 31 Dec 9999 23:59:59 GMT").is_some());
+
+        let all = [
+            "",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 Nov 1994 08:49:37 CET",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:49:37 GMT",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sunday, 06-Nov-1994 08:49:37 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+            "Sun, 06 Nov 300000000000 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 -9223372036854775808",
+            "Sat, 01 Jan 10000 00:00:00 GMT",
+            "Sat, 31 Feb 2024 00:00:00 GMT",
+            "Thu, 29 Feb 2001 00:00:00 GMT",
+            "Thu, 31 Apr 2001 00:00:00 GMT",
+        ];
+        for input in all {
+            assert_eq!(None, parse_http_date(input), "{:?}", input);
+        }
+    }
+}
@@ -1,10 +1,13 @@
+mod date;
 mod request;
 mod processing;
 mod response;
 
-use std::io::ErrorKind;
+use std::{io::ErrorKind, path::PathBuf, sync::Arc};
 
-use processing::Router;
+use processing::{MimeTypes, PathLocks, Router, SymlinkPolicy};
+use request::{Header, Limits, Method, ParseError, Parsed, Request, Version};
+use response::{Body, Connection, Headers, Response, Status};
 use tokio::{
     io::AsyncWriteExt,
     net::{
@@ -20,6 +23,45 @@ use clap::Parser;
 struct Args {
     #[arg(short, long)]
     directory: Option<String>,
+
+    /// Longest request line accepted, longer ones are answered with 414
+    #[arg(long, default_value_t = Limits::default().max_request_line)]
+    max_request_line: usize,
+
+    /// Most bytes of header lines accepted, more are answered with 431
+    #[arg(long, default_value_t = Limits::default().max_header_bytes)]
+    max_header_bytes: usize,
+
+    /// Most header fields accepted, more are answered with 431
+    #[arg(long, default_value_t = Limits::default().max_headers)]
+    max_headers: usize,
+
+    /// Largest request body accepted, larger ones are answered with 413
+    #[arg(long, default_value_t = Limits::default().max_body)]
+    max_body: usize,
+
+    /// Which symlinks within the directory are followed
+    #[arg(long, value_enum, default_value_t = SymlinkPolicy::default())]
+    symlinks: SymlinkPolicy,
+
+    /// A mime.types file, its extensions take precedence over the built-in ones
+    #[arg(long)]
+    mime_types: Option<PathBuf>,
+
+    /// Guess the type of files with unknown extensions from their content
+    #[arg(long)]
+    sniff: bool,
+}
+
+impl Args {
+    fn limits(&self) -> Limits {
+        Limits {
+            max_request_line: self.max_request_line,
+            max_header_bytes: self.max_header_bytes,
+            max_headers: self.max_headers,
+            max_body: self.max_body,
+        }
+    }
 }
 
 #[tokio::main]
@@ -29,69 +71,232 @@ async fn main() -> anyhow::Result<()> {
     // You can use print statements as follows for debugging, they'll be visible when running tests.
     println!("Logs from your program will appear here!");
 
+    let mut mime = MimeTypes::new(args.sniff);
+    if let Some(path) = &args.mime_types {
+        mime = mime.with_overrides(&std::fs::read_to_string(path)?)?;
+    }
+    let mime = Arc::new(mime);
+    let locks = Arc::new(PathLocks::default());
+
     let listener = TcpListener::bind("127.0.0.1:4221").await?;
 
     loop {
         let (socket, _) = listener.accept().await?;
-        let directory = args.directory.clone();
+        let router = Router {
+            directory: args.directory.clone(),
+            symlinks: args.symlinks,
+            mime: mime.clone(),
+            locks: locks.clone(),
+        };
+        let limits = args.limits();
 
         tokio::spawn(async move {
-            handle_connection(socket, directory)
-                .await
-                .expect("Unable to handle the connection");
+            if let Err(err) = handle_connection(socket, router, limits).await {
+                eprintln!("unable to handle the connection: {:?}", err);
+            }
         });
     }
 }
 
-async fn handle_connection(stream: TcpStream, directory: Option<String>) -> anyhow::Result<()> {
+async fn handle_connection(stream: TcpStream, r: Router, limits: Limits) -> anyhow::Result<()> {
     let (reader, mut writer) = stream.into_split();
     let mut in_buf = Vec::with_capacity(4 * 1024);
     let mut out_buf = Vec::with_capacity(4 * 1024);
-    let r = Router { directory };
+    let mut parser = request::Parser::new(limits);
 
     loop {
-        in_buf.clear();
         out_buf.clear();
 
-        // wait until the channel is reable
-        reader.readable().await?;
-
-        let (size, closed) = load_request(&reader, &mut in_buf).await?;
-
-        if closed {
-            break Ok(());
-        }
-
-        let (request, _) = request::parse(&in_buf[..size])?;
+        let loaded = load_request(&reader, &mut writer, &r, &mut parser, &mut in_buf).await;
+        let request = match loaded {
+            Ok(Loaded::Request(request)) => request,
+            Ok(Loaded::Closed) => break Ok(()),
+            Ok(Loaded::Rejected(mut resp)) => {
+                // the client may still send the body, we won't read it
+                resp.headers.insert(Headers::Connection(Connection::Close));
+                resp.write(&mut out_buf);
+                write_response(&mut writer, &out_buf).await?;
+                break Ok(());
+            }
+            Err(err) => match err.downcast::<ParseError>() {
+                Ok(err) => {
+                    // we can't know where the next request would start
+                    eprintln!("unable to parse the request: {}", err);
+                    parse_error(&err).write(&mut out_buf);
+                    write_response(&mut writer, &out_buf).await?;
+                    break Ok(());
+                }
+                Err(err) => break Err(err),
+            },
+        };
         println!("{:?}", request);
 
-        let resp = r.process(&request).await;
-        resp.write(&mut out_buf);
+        let mut resp = r.process(&request).await;
 
-        write_response(&mut writer, &out_buf).await?;
+        // a body of unknown length can only be delimited by closing the
+        // connection for HTTP/1.0 clients
+        let keep_alive = request.header.keep_alive() && !resp.is_close_delimited();
 
-        if let Some(v) = request.header.headers.get("Connection") {
-            if v == "keep-alive" {
-                continue;
+        // on a persistent connection the client can only tell where a
+        // response without a body ends if it is told so explicitly, unless
+        // its status rules out a body anyway
+        if resp.body.is_none() && resp.status.allows_content() {
+            resp.body = Some(Body::Bytes(vec![]));
+        }
+        match (request.header.version, keep_alive) {
+            (Version::Http10, true) => {
+                resp.headers
+                    .insert(Headers::Connection(Connection::KeepAlive));
             }
-        } else {
+            (_, false) => {
+                resp.headers.insert(Headers::Connection(Connection::Close));
+            }
+            _ => {}
+        }
+
+        // the body is only read as fast as the client takes it
+        let with_body = request.header.method != Method::Head;
+        resp.send(&mut writer, with_body).await?;
+
+        if !keep_alive {
             break Ok(());
         }
     }
 }
 
+/// The response for a request that couldn't be parsed, the connection is
+/// closed afterwards.
+fn parse_error(err: &ParseError) -> Response {
+    let status = match err {
+        ParseError::BadRequestLine
+        | ParseError::InvalidUrl(_)
+        | ParseError::BadHeader(_)
+        | ParseError::BadHost(_)
+        | ParseError::InvalidContentLength
+        | ParseError::InvalidChunkedBody
+        | ParseError::Incomplete => Status::BadRequest,
+        ParseError::UnsupportedVersion(_) => Status::HttpVersionNotSupported,
+        ParseError::UriTooLong => Status::UriTooLong,
+        ParseError::HeadersTooLarge => Status::RequestHeaderFieldsTooLarge,
+        ParseError::BodyTooLarge => Status::ContentTooLarge,
+    };
+
+    let mut resp = status_only(Version::Http11, status);
+    resp.headers.insert(Headers::Connection(Connection::Close));
+    resp
+}
+
+/// A response without any headers or body, e.g. for interim responses.
+fn status_only(version: Version, status: Status) -> Response {
+    Response {
+        version,
+        status,
+        headers: Default::default(),
+        accept_encoding: None,
+        body: None,
+    }
+}
+
 async fn write_response(writer: &mut OwnedWriteHalf, buf: &[u8]) -> anyhow::Result<()> {
     writer.write_all(buf).await?;
     Ok(())
 }
 
+/// What `load_request` found on the connection.
+enum Loaded {
+    Request(Request),
+    /// The client closed the connection before a full request arrived.
+    Closed,
+    /// The client is waiting for an interim response before sending the body,
+    /// but the request is turned down with this final response instead.
+    Rejected(Response),
+}
+
+/// Feeds the parser until it has a complete request, `in_buf` holds the bytes
+/// received but not fed yet, i.e. those of pipelined requests after it.
+///
+/// The parser rejects a request as soon as it exceeds any of its limits, so
+/// that a request can't grow without bounds.
+///
+/// Clients sending `Expect: 100-continue` are told whether to send the body,
+/// once the router had a look at the header.
 async fn load_request(
     reader: &OwnedReadHalf,
+    writer: &mut OwnedWriteHalf,
+    router: &Router,
+    parser: &mut request::Parser,
     in_buf: &mut Vec<u8>,
-) -> anyhow::Result<(usize, bool)> {
-    // load all the data
-    let mut size = 0;
-    let mut buf = [0; 1024];
+) -> anyhow::Result<Loaded> {
+    let mut expectation_checked = false;
+
+    loop {
+        if in_buf.is_empty() && read_chunk(reader, in_buf).await? == 0 {
+            return Ok(Loaded::Closed);
+        }
+
+        // the parser keeps a partial request, so every byte is fed only once
+        let parsed = parser.feed(in_buf);
+        let header = match &parsed {
+            Parsed::NeedMore => parser.header(),
+            Parsed::Done(request, _) => Some(&request.header),
+            Parsed::Error(err) => Err(err.clone())?,
+        };
+
+        if let Some(header) = header.filter(|_| !expectation_checked) {
+            expectation_checked = true;
+
+            match check_expectation(header, router) {
+                Some(Err(resp)) => return Ok(Loaded::Rejected(resp)),
+                // no need to ask for a body, which is already on its way
+                Some(Ok(())) if parsed == Parsed::NeedMore && parser.body_bytes() == 0 => {
+                    let mut buf = vec![];
+                    status_only(header.version, Status::Continue).write(&mut buf);
+                    write_response(writer, &buf).await?;
+                }
+                _ => {}
+            }
+        }
+
+        match parsed {
+            Parsed::Done(request, consumed) => {
+                // anything after the request belongs to the next one
+                in_buf.drain(..consumed);
+                return Ok(Loaded::Request(*request));
+            }
+            _ => in_buf.clear(),
+        }
+    }
+}
+
+/// Checks the `Expect` field of a request, `None` if there is none to check,
+/// otherwise whether the client may go on sending the body or the final
+/// response if it may not.
+fn check_expectation(header: &Header, router: &Router) -> Option<Result<(), Response>> {
+    let expect = header.headers.get("expect")?;
+
+    // HTTP/1.0 clients don't know about expectations (RFC 9110 section 10.1.1)
+    if header.version != Version::Http11 {
+        return None;
+    }
+
+    if !expect
+        .to_str()
+        .is_some_and(|e| e.eq_ignore_ascii_case("100-continue"))
+    {
+        let resp = status_only(header.version, Status::ExpectationFailed);
+        return Some(Err(resp));
+    }
+
+    match router.check(header) {
+        Some(resp) => Some(Err(resp)),
+        None => Some(Ok(())),
+    }
+}
+
+/// Appends whatever is currently available on the socket to `in_buf`,
+/// returns the amount of bytes read, `0` meaning the connection was closed.
+async fn read_chunk(reader: &OwnedReadHalf, in_buf: &mut Vec<u8>) -> anyhow::Result<usize> {
+    let mut buf = [0; 4 * 1024];
 
     loop {
         // wait for the stream to become readable
@@ -100,14 +305,10 @@ async fn load_request(
         match reader.try_read(&mut buf) {
             Err(ref err) if err.kind() == ErrorKind::WouldBlock => continue,
             Err(err) => Err(err)?,
-            Ok(0) => break Ok((size, true)),
             Ok(n) => {
                 // copy read data into main buffer
                 in_buf.extend_from_slice(&buf[..n]);
-                size += n;
-                if n < buf.len() {
-                    break Ok((size, false));
-                }
+                break Ok(n);
             }
         }
     }
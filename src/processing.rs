@@ -1,18 +1,61 @@
-use std::path::PathBuf;
+mod listing;
+mod locks;
+mod mime;
+mod sandbox;
 
-use tokio::fs::{read, try_exists};
+use std::{
+    collections::BTreeSet,
+    fs::Metadata,
+    io::{self, Cursor, ErrorKind, SeekFrom},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use listing::{Format, Listing, Options};
+use mime::SNIFF_LEN;
+use sandbox::SandboxError;
+use tokio::{
+    fs::{create_dir_all, metadata, remove_file, rename, write, File},
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt},
+};
 
 use crate::{
-    request::{Method, Request, Version},
-    response::{ContentType, Headers, Response, Status},
+    request::{
+        multipart::MultipartError, Encoding, EntityTag, Header, HeaderMap, HeaderValue, Method,
+        Precondition, Request, TargetForm, Validators,
+    },
+    response::{Body, ContentRange, ContentType, Headers, Response, Status},
 };
 
+pub use locks::PathLocks;
+pub use mime::MimeTypes;
+pub use sandbox::SymlinkPolicy;
+
+/// The most ranges served for a single request, requests for more are
+/// answered with the whole content.
+const MAX_RANGES: usize = 16;
+
+#[derive(Debug, Clone)]
 pub struct Router {
     pub directory: Option<String>,
+    pub symlinks: SymlinkPolicy,
+    pub mime: Arc<MimeTypes>,
+    /// Shared by all connections, held while a file is changed.
+    pub locks: Arc<PathLocks>,
 }
 
 impl Router {
     pub async fn process(&self, request: &Request) -> Response {
+        if let Err(resp) = self.validate(request) {
+            return resp;
+        }
+
         match &request.header.url.sections[0][..] {
             "/" => self.root(request),
             "echo" => self.echo(request),
@@ -22,6 +65,81 @@ impl Router {
         }
     }
 
+    /// Decides based on the header alone whether the request would be served,
+    /// so that it can be turned down before its body is read.
+    ///
+    /// Returns the final response if the request won't be served.
+    pub fn check(&self, header: &Header) -> Option<Response> {
+        let request = Request {
+            header: header.clone(),
+            body: None,
+            trailers: HeaderMap::new(),
+        };
+        self.validate(&request).err()
+    }
+
+    /// Checks the route, the method and the acceptable codings, `Err` holds
+    /// the final response for requests that don't reach a handler.
+    fn validate(&self, request: &Request) -> Result<(), Response> {
+        // `OPTIONS *` asks about the server as a whole
+        if request.header.url.form == TargetForm::Asterisk {
+            let all = vec![
+                Method::Get,
+                Method::Head,
+                Method::Post,
+                Method::Put,
+                Method::Delete,
+                Method::Options,
+            ];
+            return Err(Self::options(request, all));
+        }
+
+        let route = &request.header.url.sections[0][..];
+
+        let Some(allowed) = Self::allowed_methods(route) else {
+            return Err(Self::not_found(request));
+        };
+
+        match &request.header.method {
+            // we aren't a proxy, so there are no tunnels to establish
+            Method::Extension(_) | Method::Connect => return Err(Self::not_implemented(request)),
+            Method::Options => return Err(Self::options(request, allowed)),
+            method if !allowed.contains(method) => {
+                return Err(Self::method_not_allowed(request, allowed))
+            }
+            _ => {}
+        }
+
+        if route == "files" && self.directory.is_none() {
+            return Err(Self::internal_server_error(request));
+        }
+
+        // turned down before the handler runs, which may change files even
+        // if its response couldn't be sent
+        if request.header.no_acceptable_encoding() {
+            return Err(Self::not_acceptable(request));
+        }
+
+        Ok(())
+    }
+
+    /// The methods each route knows how to handle, `None` for unknown routes.
+    fn allowed_methods(route: &str) -> Option<Vec<Method>> {
+        let methods = match route {
+            "/" | "echo" | "user-agent" => vec![Method::Get, Method::Head, Method::Options],
+            "files" => vec![
+                Method::Get,
+                Method::Head,
+                Method::Post,
+                Method::Put,
+                Method::Delete,
+                Method::Options,
+            ],
+            _ => return None,
+        };
+        Some(methods)
+    }
+
     fn root(&self, request: &Request) -> Response {
         Self::ok(request)
     }
@@ -35,56 +153,221 @@ impl Router {
 
         let mut resp = Self::ok(request);
 
-        let ct = Headers::ContentType(ContentType::TextPlain);
+        let ct = Headers::ContentType(ContentType::TEXT_PLAIN);
         resp.headers.insert(ct);
-        resp.body = Some(sections[1].as_bytes().to_vec());
+        resp.body = Some(sections[1].as_bytes().to_vec().into());
         resp
     }
 
     fn user_agent(&self, request: &Request) -> Response {
         let mut resp = Self::ok(request);
-        let ct = Headers::ContentType(ContentType::TextPlain);
+        let ct = Headers::ContentType(ContentType::TEXT_PLAIN);
 
         resp.headers.insert(ct);
-        resp.body = Some(request.header.headers["user-agent"].as_bytes().to_vec());
+        let user_agent = request.header.headers.get("user-agent");
+        resp.body = Some(
+            user_agent
+                .map(HeaderValue::as_bytes)
+                .unwrap_or_default()
+                .to_vec()
+                .into(),
+        );
         resp
     }
 
     async fn files(&self, request: &Request) -> Response {
         match request.header.method {
-            Method::Get => self.files_get(request).await,
+            Method::Get | Method::Head => self.files_get(request).await,
             Method::Post => self.files_post(request).await,
+            Method::Put => self.files_put(request).await,
+            Method::Delete => self.files_delete(request).await,
+            _ => Self::method_not_allowed(
+                request,
+                Self::allowed_methods("files").unwrap_or_default(),
+            ),
         }
     }
 
     async fn files_get(&self, request: &Request) -> Response {
-        let sections = &request.header.url.sections;
+        // `/files` itself lists the configured directory
+        let path = match self.resolve(request).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
 
-        if sections.len() == 1 {
-            return Self::not_found(request);
+        let mut file = match File::open(&path).await {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Self::not_found(request),
+            Err(_) => return Self::internal_server_error(request),
+            Ok(file) => file,
+        };
+        let metadata = match file.metadata().await {
+            Err(_) => return Self::internal_server_error(request),
+            Ok(metadata) => metadata,
+        };
+        if metadata.is_dir() {
+            return self.files_list(request, &path).await;
+        }
+
+        // ranges are only ever served without a content coding, a full body
+        // may be compressed, which makes it a representation of its own
+        let validators = Self::validators(&metadata);
+        let coding = request
+            .header
+            .accept_encoding
+            .filter(|_| metadata.len() > 0);
+        let full = Self::coded(&validators, coding);
+
+        match request.header.preconditions(Some(&full)) {
+            Precondition::Proceed => {}
+            Precondition::NotModified => {
+                let mut resp = Self::not_modified(request);
+                Self::insert_validators(&mut resp, full);
+                return resp;
+            }
+            Precondition::Failed => return Self::precondition_failed(request),
+        }
+
+        // only the start of the file is needed to tell its type, the content
+        // itself is read while it is sent
+        let mut head = vec![];
+        let read_head = async {
+            (&mut file)
+                .take(SNIFF_LEN as u64)
+                .read_to_end(&mut head)
+                .await?;
+            file.rewind().await
+        };
+        if let Err(err) = read_head.await {
+            eprintln!("error {:?}", err);
+            return Self::internal_server_error(request);
         }
-        match &self.directory {
-            None => Self::internal_server_error(request),
-            Some(directory) => {
-                let mut file = PathBuf::from(&directory[..]);
-                file.push(&sections[1]);
-
-                match try_exists(&file).await {
-                    Err(_) => Self::internal_server_error(request),
-                    Ok(false) => Self::not_found(request),
-                    Ok(true) => match read(file).await {
-                        Err(_) => Self::internal_server_error(request),
-                        Ok(content) => {
-                            let mut resp = Self::ok(request);
-                            let ct = Headers::ContentType(ContentType::OctentStream);
-                            resp.headers.insert(ct);
-                            resp.body = Some(content);
-
-                            resp
-                        }
-                    },
+        let content_type = self.mime.content_type(&path, &head);
+        let len = metadata.len();
+
+        let mut resp = match Self::ranges(request, &validators, len) {
+            Some(ranges) => {
+                match Self::partial(request, &path, file, len, &ranges, content_type).await {
+                    Ok(resp) => resp,
+                    Err(err) => {
+                        eprintln!("error {:?}", err);
+                        return Self::internal_server_error(request);
+                    }
                 }
             }
+            None => {
+                let mut resp = Self::ok(request);
+                resp.headers.insert(Headers::ContentType(content_type));
+                resp.headers.insert(Headers::AcceptRanges);
+                resp.body = Some(Body::file(file, len));
+                resp
+            }
+        };
+        match resp.status {
+            Status::PartialContent => Self::insert_validators(&mut resp, validators),
+            Status::RangeNotSatisfiable => {}
+            _ => Self::insert_validators(&mut resp, full),
+        }
+        resp
+    }
+
+    /// Lists the entries of a directory as HTML or JSON, the format is taken
+    /// from the query string or negotiated with `Accept`.
+    async fn files_list(&self, request: &Request, path: &Path) -> Response {
+        let query = match request.query() {
+            Ok(query) => query,
+            Err(err) => {
+                eprintln!("error {}", err);
+                return Self::bad_request(request);
+            }
+        };
+        let options = match Options::from_query(&query) {
+            Ok(options) => options,
+            Err(err) => {
+                eprintln!("error {}", err);
+                return Self::bad_request(request);
+            }
+        };
+
+        let offers = Format::ALL.map(|f| f.media_type());
+        let format = match options.format {
+            Some(format) => format,
+            None => match request.header.preferred_media_type(&offers) {
+                Some(media_type) => Format::from_media_type(media_type).unwrap_or(Format::Html),
+                None => return Self::not_acceptable(request),
+            },
+        };
+
+        let Some(directory) = &self.directory else {
+            return Self::internal_server_error(request);
+        };
+        let dir = request.header.url.sections[1..].to_vec();
+
+        let listing = match Listing::read(path, dir, Path::new(directory), self.symlinks).await {
+            Ok(listing) => listing,
+            Err(err) => {
+                eprintln!("error {:?}", err);
+                return Self::internal_server_error(request);
+            }
+        };
+
+        let mut resp = Self::ok(request);
+        resp.headers
+            .insert(Headers::ContentType(format.content_type()));
+        if options.format.is_none() {
+            resp.headers.insert(Headers::Vary("Accept"));
+        }
+        resp.body = Some(listing.render(&options, format).into());
+        resp
+    }
+
+    /// The validators of a file, the entity tag changes whenever the file is
+    /// modified or changes its size.
+    fn validators(metadata: &Metadata) -> Validators {
+        let modified = metadata.modified().ok();
+        let etag = EntityTag::strong(&format!(
+            "{:x}-{:x}",
+            modified
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default()
+                .as_nanos(),
+            metadata.len()
+        ));
+
+        // dates are only exchanged with a precision of seconds
+        let last_modified = modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| UNIX_EPOCH + Duration::from_secs(d.as_secs()));
+
+        Validators {
+            etag: Some(etag),
+            last_modified,
+        }
+    }
+
+    /// The validators of a body sent with `coding`, a strong tag has to
+    /// change along with the bytes sent (RFC 9110 section 8.8.3).
+    fn coded(validators: &Validators, coding: Option<Encoding>) -> Validators {
+        let suffix = match coding {
+            None | Some(Encoding::Identity) => return validators.clone(),
+            Some(Encoding::Gzip) => "gz",
+            Some(Encoding::Deflate) => "df",
+        };
+
+        Validators {
+            etag: validators.etag.as_ref().map(|etag| EntityTag {
+                weak: etag.weak,
+                tag: format!("{}-{}", etag.tag, suffix),
+            }),
+            last_modified: validators.last_modified,
+        }
+    }
+
+    fn insert_validators(resp: &mut Response, validators: Validators) {
+        if let Some(etag) = validators.etag {
+            resp.headers.insert(Headers::ETag(etag));
+        }
+        if let Some(last_modified) = validators.last_modified {
+            resp.headers.insert(Headers::LastModified(last_modified));
         }
     }
 
@@ -92,31 +375,306 @@ impl Router {
         let sections = &request.header.url.sections;
 
         if sections.len() == 1 {
-            return Self::not_found(request);
+            return self.files_upload(request).await;
         }
-        match &self.directory {
-            None => Self::internal_server_error(request),
-            Some(directory) => {
-                let mut path = PathBuf::from(directory);
-                path.push(&sections[1]);
-
-                match &request.body {
-                    None => Self::internal_server_error(request),
-                    Some(content) => {
-                        if let Err(err) = tokio::fs::write(path, content).await {
-                            eprintln!("error {:?}", err);
-                            return Self::internal_server_error(request);
-                        }
-                        Self::created(request)
-                    }
+        let path = match self.resolve(request).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        let Some(content) = &request.body else {
+            return Self::internal_server_error(request);
+        };
+
+        let _guard = self.locks.lock(&path).await;
+        if let Ok(true) = metadata(&path).await.map(|m| m.is_dir()) {
+            return Self::conflict(request);
+        }
+        if let Err(err) = replace_file(&path, content).await {
+            eprintln!("error {:?}", err);
+            return Self::internal_server_error(request);
+        }
+        Self::created(request)
+    }
+
+    /// Creates or replaces a file, answered with 201 and 204 respectively.
+    ///
+    /// `If-None-Match: *` only creates the file if there is none yet,
+    /// `If-Match` only replaces the version the client knows about.
+    async fn files_put(&self, request: &Request) -> Response {
+        let path = match self.resolve(request).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+
+        // nobody else may change the file until it is replaced
+        let _guard = self.locks.lock(&path).await;
+        let current = match Self::current(request, &path).await {
+            Ok(current) => current,
+            Err(resp) => return resp,
+        };
+
+        if request.header.preconditions(current.as_ref()) != Precondition::Proceed {
+            return Self::precondition_failed(request);
+        }
+
+        let content = request.body.as_deref().unwrap_or_default();
+        if let Err(err) = replace_file(&path, content).await {
+            eprintln!("error {:?}", err);
+            return Self::internal_server_error(request);
+        }
+
+        match current {
+            Some(_) => Self::no_content(request),
+            None => Self::created(request),
+        }
+    }
+
+    async fn files_delete(&self, request: &Request) -> Response {
+        let path = match self.resolve(request).await {
+            Ok(path) => path,
+            Err(resp) => return resp,
+        };
+        let _guard = self.locks.lock(&path).await;
+        let current = match Self::current(request, &path).await {
+            Ok(Some(current)) => current,
+            Ok(None) => return Self::not_found(request),
+            Err(resp) => return resp,
+        };
+
+        if request.header.preconditions(Some(&current)) != Precondition::Proceed {
+            return Self::precondition_failed(request);
+        }
+
+        match remove_file(&path).await {
+            Ok(()) => Self::no_content(request),
+            Err(err) if err.kind() == ErrorKind::NotFound => Self::not_found(request),
+            Err(err) => {
+                eprintln!("error {:?}", err);
+                Self::internal_server_error(request)
+            }
+        }
+    }
+
+    /// The validators of the file at `path`, `None` if there is none yet.
+    ///
+    /// Directories can't be replaced or deleted, `Err` holds the final
+    /// response then.
+    async fn current(request: &Request, path: &Path) -> Result<Option<Validators>, Response> {
+        match metadata(path).await {
+            Ok(metadata) if metadata.is_dir() => Err(Self::conflict(request)),
+            Ok(metadata) => Ok(Some(Self::validators(&metadata))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                eprintln!("error {:?}", err);
+                Err(Self::internal_server_error(request))
+            }
+        }
+    }
+
+    /// The path below the configured directory the request refers to, `Err`
+    /// holds the final response if it can't be served.
+    async fn resolve(&self, request: &Request) -> Result<PathBuf, Response> {
+        let Some(directory) = &self.directory else {
+            return Err(Self::internal_server_error(request));
+        };
+
+        let segments = &request.header.url.sections[1..];
+        match sandbox::resolve(Path::new(directory), segments, self.symlinks).await {
+            Ok(path) => Ok(path),
+            Err(SandboxError::Escape | SandboxError::Symlink) => Err(Self::forbidden(request)),
+            // there is nothing to find, but nowhere to create it either
+            Err(SandboxError::NotADirectory(_)) => match request.header.method {
+                Method::Get | Method::Head | Method::Delete => Err(Self::not_found(request)),
+                _ => Err(Self::conflict(request)),
+            },
+            Err(SandboxError::Io(err)) => {
+                eprintln!("error {:?}", err);
+                Err(Self::internal_server_error(request))
+            }
+        }
+    }
+
+    /// The ranges a GET asks for, `None` if the whole content is to be sent
+    /// instead (RFC 9110 section 14.2), empty if none of them is satisfiable.
+    fn ranges(
+        request: &Request,
+        validators: &Validators,
+        len: u64,
+    ) -> Option<Vec<RangeInclusive<u64>>> {
+        if request.header.method != Method::Get {
+            return None;
+        }
+        let specs = request.header.range()?;
+
+        // the client only wants parts of the content it already has, a newer
+        // one is sent in full
+        if !request.header.if_range_matches(validators) {
+            return None;
+        }
+        // lots of tiny ranges only make us do a lot of work for nothing
+        if specs.len() > MAX_RANGES {
+            return None;
+        }
+
+        Some(specs.iter().filter_map(|spec| spec.resolve(len)).collect())
+    }
+
+    /// Answers with the `ranges` of the `len` bytes long file at `path`, the
+    /// first one is read from `file`, every other one from its own handle.
+    async fn partial(
+        request: &Request,
+        path: &Path,
+        file: File,
+        len: u64,
+        ranges: &[RangeInclusive<u64>],
+        content_type: ContentType,
+    ) -> io::Result<Response> {
+        // ranges are never sent with a content coding, as they are counted in
+        // bytes of the unencoded content
+        let mut resp = Response {
+            version: request.header.version,
+            status: Status::PartialContent,
+            headers: Default::default(),
+            accept_encoding: None,
+            body: None,
+        };
+        resp.headers.insert(Headers::AcceptRanges);
+
+        let content_range = |range: &RangeInclusive<u64>| ContentRange {
+            range: Some((*range.start(), *range.end())),
+            len,
+        };
+        let mut file = Some(file);
+        let mut open_part = |range: RangeInclusive<u64>| {
+            let file = file.take();
+            async move {
+                let mut file = match file {
+                    Some(file) => file,
+                    None => File::open(path).await?,
+                };
+                file.seek(SeekFrom::Start(*range.start())).await?;
+                Ok::<_, io::Error>(file.take(range.end() - range.start() + 1))
+            }
+        };
+
+        match ranges {
+            [] => {
+                resp.status = Status::RangeNotSatisfiable;
+                let content_range = ContentRange { range: None, len };
+                resp.headers.insert(Headers::ContentRange(content_range));
+            }
+            [range] => {
+                let part = open_part(range.clone()).await?;
+                resp.headers
+                    .insert(Headers::ContentRange(content_range(range)));
+                resp.headers.insert(Headers::ContentType(content_type));
+                resp.body = Some(Body::stream(part, Some(range.end() - range.start() + 1)));
+            }
+            ranges => {
+                let boundary = format!(
+                    "{:x}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_nanos()
+                );
+
+                let mut body: Pin<Box<dyn AsyncRead + Send + Sync>> = Box::pin(tokio::io::empty());
+                let mut body_len = 0;
+                for range in ranges {
+                    let part_header = format!(
+                        "--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                        boundary,
+                        content_type.text(),
+                        content_range(range).text()
+                    );
+                    let part = open_part(range.clone()).await?;
+
+                    body_len += part_header.len() as u64 + part.limit() + 2;
+                    body = Box::pin(
+                        body.chain(Cursor::new(part_header.into_bytes()))
+                            .chain(part)
+                            .chain(&b"\r\n"[..]),
+                    );
                 }
+                let closing = format!("--{}--\r\n", boundary);
+                body_len += closing.len() as u64;
+                let body = body.chain(Cursor::new(closing.into_bytes()));
+
+                let ct = ContentType::multipart_byteranges(boundary);
+                resp.headers.insert(Headers::ContentType(ct));
+                resp.body = Some(Body::stream(body, Some(body_len)));
             }
         }
+
+        Ok(resp)
+    }
+
+    /// Stores every file of a `multipart/form-data` upload under its filename
+    /// and lists the names of the created files.
+    async fn files_upload(&self, request: &Request) -> Response {
+        let Some(directory) = &self.directory else {
+            return Self::internal_server_error(request);
+        };
+        let root = Path::new(directory);
+
+        let parts = match request.multipart() {
+            Ok(parts) => parts,
+            Err(MultipartError::NotMultipart) => return Self::unsupported_media_type(request),
+            Err(_) => return Self::bad_request(request),
+        };
+
+        let mut created = vec![];
+        for part in parts {
+            let Some(filename) = &part.filename else {
+                continue;
+            };
+
+            // some clients send the full path, only the final component is used
+            let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+            if name.is_empty() || name == "." || name == ".." {
+                return Self::bad_request(request);
+            }
+
+            // an existing symlink of that name mustn't be written through
+            let segments = [name.to_string()];
+            let path = match sandbox::resolve(root, &segments, self.symlinks).await {
+                Ok(path) => path,
+                Err(SandboxError::Io(err)) => {
+                    eprintln!("error {:?}", err);
+                    return Self::internal_server_error(request);
+                }
+                Err(_) => return Self::forbidden(request),
+            };
+
+            let _guard = self.locks.lock(&path).await;
+            if let Err(err) = replace_file(&path, part.body).await {
+                eprintln!("error {:?}", err);
+                return Self::internal_server_error(request);
+            }
+            created.push(name.to_string());
+        }
+
+        if created.is_empty() {
+            return Self::bad_request(request);
+        }
+
+        let mut resp = Self::created(request);
+        resp.headers
+            .insert(Headers::ContentType(ContentType::TEXT_PLAIN));
+        resp.body = Some(
+            created
+                .iter()
+                .map(|name| format!("{}\n", name))
+                .collect::<String>()
+                .into(),
+        );
+        resp
     }
 
     fn created(request: &Request) -> Response {
         Response {
-            version: Version::Http11,
+            version: request.header.version,
             status: Status::Created,
             headers: Default::default(),
             accept_encoding: request.header.accept_encoding,
@@ -124,9 +682,19 @@ impl Router {
         }
     }
 
+    fn no_content(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::NoContent,
+            headers: Default::default(),
+            accept_encoding: None,
+            body: None,
+        }
+    }
+
     fn ok(request: &Request) -> Response {
         Response {
-            version: Version::Http11,
+            version: request.header.version,
             status: Status::Ok,
             headers: Default::default(),
             accept_encoding: request.header.accept_encoding,
@@ -134,9 +702,59 @@ impl Router {
         }
     }
 
+    fn not_modified(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::NotModified,
+            headers: Default::default(),
+            accept_encoding: None,
+            body: None,
+        }
+    }
+
+    fn conflict(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::Conflict,
+            headers: Default::default(),
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
+    fn precondition_failed(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::PreconditionFailed,
+            headers: Default::default(),
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
+    fn bad_request(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::BadRequest,
+            headers: Default::default(),
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
+    fn forbidden(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::Forbidden,
+            headers: Default::default(),
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
     fn not_found(request: &Request) -> Response {
         Response {
-            version: Version::Http11,
+            version: request.header.version,
             status: Status::NotFound,
             headers: Default::default(),
             accept_encoding: request.header.accept_encoding,
@@ -144,9 +762,58 @@ impl Router {
         }
     }
 
+    fn options(request: &Request, allowed: Vec<Method>) -> Response {
+        let mut resp = Self::ok(request);
+        resp.headers.insert(Headers::Allow(allowed));
+        resp
+    }
+
+    fn method_not_allowed(request: &Request, allowed: Vec<Method>) -> Response {
+        let mut headers = BTreeSet::new();
+        headers.insert(Headers::Allow(allowed));
+
+        Response {
+            version: request.header.version,
+            status: Status::MethodNotAllowed,
+            headers,
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
+    fn not_acceptable(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::NotAcceptable,
+            headers: Default::default(),
+            accept_encoding: None,
+            body: None,
+        }
+    }
+
+    fn unsupported_media_type(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::UnsupportedMediaType,
+            headers: Default::default(),
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
+    fn not_implemented(request: &Request) -> Response {
+        Response {
+            version: request.header.version,
+            status: Status::NotImplemented,
+            headers: Default::default(),
+            accept_encoding: request.header.accept_encoding,
+            body: None,
+        }
+    }
+
     fn internal_server_error(request: &Request) -> Response {
         Response {
-            version: Version::Http11,
+            version: request.header.version,
             status: Status::InternalServerError,
             headers: Default::default(),
             accept_encoding: request.header.accept_encoding,
@@ -154,3 +821,380 @@ impl Router {
         }
     }
 }
+
+/// Writes `content` to a new file next to `path` and renames it into place,
+/// so that the file at `path` is replaced all at once, creating any missing
+/// directories on the way.
+async fn replace_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let res = async {
+        write(&temp, content).await?;
+        rename(&temp, path).await
+    }
+    .await;
+    if res.is_err() {
+        let _ = remove_file(&temp).await;
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A fresh, empty directory to serve.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("router-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn router(directory: Option<&Path>) -> Router {
+        Router {
+            directory: directory.map(|d| d.to_string_lossy().to_string()),
+            symlinks: SymlinkPolicy::default(),
+            mime: Arc::new(MimeTypes::new(false)),
+            locks: Arc::default(),
+        }
+    }
+
+    fn request(input: &str) -> Request {
+        let (request, _) = crate::request::parse(input.as_bytes()).expect("valid request");
+        request
+    }
+
+    /// Sends the response, returning its head and its body.
+    async fn send(resp: Response) -> (String, Vec<u8>) {
+        let mut buf = vec![];
+        resp.send(&mut buf, true).await.expect("sent");
+
+        let pos = buf.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let body = buf.split_off(pos);
+        (String::from_utf8(buf).unwrap(), body)
+    }
+
+    fn allow(resp: &Response) -> Option<&[Method]> {
+        resp.headers.iter().find_map(|h| match h {
+            Headers::Allow(methods) => Some(&methods[..]),
+            _ => None,
+        })
+    }
+
+    #[tokio::test]
+    async fn methods() {
+        let r = router(None);
+        let echo = [Method::Get, Method::Head, Method::Options];
+
+        let all = [
+            ("GET /echo/a HTTP/1.1\r\nHost: a\r\n\r\n", Status::Ok, None),
+            // the query is only decoded by routes that read it
+            (
+                "GET /echo/a?x=%FF HTTP/1.1\r\nHost: a\r\n\r\n",
+                Status::Ok,
+                None,
+            ),
+            (
+                "POST /echo/a HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\n\r\n",
+                Status::MethodNotAllowed,
+                Some(&echo[..]),
+            ),
+            (
+                "OPTIONS /echo/a HTTP/1.1\r\nHost: a\r\n\r\n",
+                Status::Ok,
+                Some(&echo[..]),
+            ),
+            (
+                "OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n",
+                Status::Ok,
+                Some(&[
+                    Method::Get,
+                    Method::Head,
+                    Method::Post,
+                    Method::Put,
+                    Method::Delete,
+                    Method::Options,
+                ]),
+            ),
+            (
+                "CONNECT a:443 HTTP/1.1\r\nHost: a:443\r\n\r\n",
+                Status::NotImplemented,
+                None,
+            ),
+            (
+                "PROPFIND /echo/a HTTP/1.1\r\nHost: a\r\n\r\n",
+                Status::NotImplemented,
+                None,
+            ),
+            (
+                "DELETE /nothing HTTP/1.1\r\nHost: a\r\n\r\n",
+                Status::NotFound,
+                None,
+            ),
+        ];
+
+        for (input, status, methods) in all {
+            let resp = r.process(&request(input)).await;
+            assert_eq!(status, resp.status, "{:?}", input);
+            assert_eq!(methods, allow(&resp), "{:?}", input);
+        }
+    }
+
+    #[tokio::test]
+    async fn upload() {
+        let dir = temp_dir("upload");
+        let r = router(Some(&dir));
+
+        let body = "--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.txt\"\r\n\r\nabc\r\n--b--\r\n";
+        let upload = |accept_encoding: &str| {
+            request(&format!(
+                "POST /files HTTP/1.1\r\nHost: a\r\nAccept-Encoding: {}\r\n\
+                 Content-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n{}",
+                accept_encoding,
+                body.len(),
+                body
+            ))
+        };
+
+        // nothing is written for a response that can't be sent
+        let resp = r.process(&upload("identity;q=0")).await;
+        assert_eq!(Status::NotAcceptable, resp.status);
+        assert!(!dir.join("a.txt").exists());
+
+        let resp = r.process(&upload("gzip")).await;
+        assert_eq!(Status::Created, resp.status);
+        assert_eq!(Some(Encoding::Gzip), resp.accept_encoding);
+        assert!(matches!(&resp.body, Some(Body::Bytes(b)) if b == b"a.txt\n"));
+        assert_eq!("abc", std::fs::read_to_string(dir.join("a.txt")).unwrap());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn ranges() {
+        let dir = temp_dir("ranges");
+        std::fs::write(dir.join("a.txt"), "abcdefghijklmnopqrstuvwxyz").unwrap();
+        let r = router(Some(&dir));
+
+        let get = |fields: &str| {
+            request(&format!(
+                "GET /files/a.txt HTTP/1.1\r\nHost: a\r\n{}\r\n",
+                fields
+            ))
+        };
+
+        let resp = r.process(&get("Range: bytes=2-5\r\n")).await;
+        assert_eq!(Status::PartialContent, resp.status);
+        let (head, body) = send(resp).await;
+        assert!(head.contains("Content-Range: bytes 2-5/26\r\n"), "{}", head);
+        assert!(head.contains("Content-Length: 4\r\n"), "{}", head);
+        assert_eq!(b"cdef", &body[..]);
+
+        let resp = r.process(&get("Range: bytes=0-1,-2\r\n")).await;
+        assert_eq!(Status::PartialContent, resp.status);
+        let (head, body) = send(resp).await;
+        let boundary = head
+            .split("multipart/byteranges; boundary=")
+            .nth(1)
+            .and_then(|rest| rest.split("\r\n").next())
+            .expect("multipart content type");
+        let exp = format!(
+            "--{b}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/26\r\n\r\nab\r\n\
+             --{b}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 24-25/26\r\n\r\nyz\r\n\
+             --{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(exp, String::from_utf8(body).unwrap());
+        assert!(head.contains(&format!("Content-Length: {}\r\n", exp.len())));
+
+        let resp = r.process(&get("Range: bytes=30-\r\n")).await;
+        assert_eq!(Status::RangeNotSatisfiable, resp.status);
+        let (head, _) = send(resp).await;
+        assert!(head.contains("Content-Range: bytes */26\r\n"), "{}", head);
+
+        // a range of an outdated representation isn't of any use
+        let resp = r
+            .process(&get("Range: bytes=2-5\r\nIf-Range: \"other\"\r\n"))
+            .await;
+        assert_eq!(Status::Ok, resp.status);
+        let (head, body) = send(resp).await;
+        assert_eq!(b"abcdefghijklmnopqrstuvwxyz", &body[..]);
+
+        let etag = head
+            .lines()
+            .find_map(|line| line.strip_prefix("ETag: "))
+            .expect("etag");
+        let resp = r
+            .process(&get(&format!("Range: bytes=2-5\r\nIf-Range: {}\r\n", etag)))
+            .await;
+        assert_eq!(Status::PartialContent, resp.status);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn put_and_delete() {
+        let dir = temp_dir("put");
+        let r = router(Some(&dir));
+
+        let put = |fields: &str, body: &str| {
+            request(&format!(
+                "PUT /files/d/a.txt HTTP/1.1\r\nHost: a\r\n{}Content-Length: {}\r\n\r\n{}",
+                fields,
+                body.len(),
+                body
+            ))
+        };
+        let delete = |fields: &str| {
+            request(&format!(
+                "DELETE /files/d/a.txt HTTP/1.1\r\nHost: a\r\n{}\r\n",
+                fields
+            ))
+        };
+        let etag = || async {
+            let get = request("GET /files/d/a.txt HTTP/1.1\r\nHost: a\r\n\r\n");
+            let (head, _) = send(r.process(&get).await).await;
+            head.lines()
+                .find_map(|line| line.strip_prefix("ETag: ").map(str::to_string))
+                .expect("etag")
+        };
+
+        let resp = r.process(&put("If-Match: *\r\n", "a")).await;
+        assert_eq!(Status::PreconditionFailed, resp.status);
+        let resp = r.process(&put("If-None-Match: *\r\n", "a")).await;
+        assert_eq!(Status::Created, resp.status);
+        let resp = r.process(&put("If-None-Match: *\r\n", "b")).await;
+        assert_eq!(Status::PreconditionFailed, resp.status);
+        assert_eq!("a", std::fs::read_to_string(dir.join("d/a.txt")).unwrap());
+
+        let resp = r.process(&put("If-Match: \"other\"\r\n", "b")).await;
+        assert_eq!(Status::PreconditionFailed, resp.status);
+        let fields = format!("If-Match: {}\r\n", etag().await);
+        let resp = r.process(&put(&fields, "bc")).await;
+        assert_eq!(Status::NoContent, resp.status);
+        assert_eq!("bc", std::fs::read_to_string(dir.join("d/a.txt")).unwrap());
+
+        // only one of the clients replacing the same version gets to do so
+        let fields = format!("If-Match: {}\r\n", etag().await);
+        let tasks: Vec<_> = (0..16)
+            .map(|i| {
+                let (r, put) = (r.clone(), put(&fields, &"x".repeat(3 + i)));
+                tokio::spawn(async move { r.process(&put).await.status })
+            })
+            .collect();
+        let mut replaced = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Status::NoContent => replaced += 1,
+                status => assert_eq!(Status::PreconditionFailed, status),
+            }
+        }
+        assert_eq!(1, replaced);
+
+        let resp = r.process(&delete("If-Match: \"other\"\r\n")).await;
+        assert_eq!(Status::PreconditionFailed, resp.status);
+        let resp = r.process(&delete("")).await;
+        assert_eq!(Status::NoContent, resp.status);
+        assert!(!dir.join("d/a.txt").exists());
+        let resp = r.process(&delete("")).await;
+        assert_eq!(Status::NotFound, resp.status);
+
+        // no temporary files are left behind
+        assert_eq!(0, std::fs::read_dir(dir.join("d")).unwrap().count());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn stream_large_file() {
+        let dir = temp_dir("stream");
+        // several times the size of the pieces a body is sent in
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.join("large.bin"), &content).unwrap();
+        let r = router(Some(&dir));
+
+        let resp = r
+            .process(&request("GET /files/large.bin HTTP/1.1\r\nHost: a\r\n\r\n"))
+            .await;
+        assert_eq!(Status::Ok, resp.status);
+        assert!(
+            matches!(resp.body, Some(Body::File { .. })),
+            "{:?}",
+            resp.body
+        );
+
+        let (head, body) = send(resp).await;
+        assert!(
+            head.contains(&format!("Content-Length: {}\r\n", content.len())),
+            "{}",
+            head
+        );
+        assert_eq!(content, body);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn coded_etags() {
+        let dir = temp_dir("etags");
+        std::fs::write(dir.join("a.txt"), "abcdefghijklmnopqrstuvwxyz").unwrap();
+        let r = router(Some(&dir));
+
+        let get = |fields: &str| {
+            request(&format!(
+                "GET /files/a.txt HTTP/1.1\r\nHost: a\r\n{}\r\n",
+                fields
+            ))
+        };
+        let etag = |resp: &Response| {
+            resp.headers.iter().find_map(|h| match h {
+                Headers::ETag(etag) => Some(etag.text()),
+                _ => None,
+            })
+        };
+
+        let identity = etag(&r.process(&get("")).await).expect("etag");
+        let gzip = etag(&r.process(&get("Accept-Encoding: gzip\r\n")).await).expect("etag");
+        assert_ne!(identity, gzip);
+
+        let resp = r
+            .process(&get(&format!("If-None-Match: {}\r\n", gzip)))
+            .await;
+        assert_eq!(Status::Ok, resp.status);
+        let fields = format!("Accept-Encoding: gzip\r\nIf-None-Match: {}\r\n", gzip);
+        let resp = r.process(&get(&fields)).await;
+        assert_eq!(Status::NotModified, resp.status);
+        assert_eq!(Some(gzip.clone()), etag(&resp));
+
+        // ranges are counted in bytes of the uncoded file
+        let fields = format!(
+            "Accept-Encoding: gzip\r\nRange: bytes=0-1\r\nIf-Range: {}\r\n",
+            gzip
+        );
+        let resp = r.process(&get(&fields)).await;
+        assert_eq!(Status::Ok, resp.status);
+        let fields = format!(
+            "Accept-Encoding: gzip\r\nRange: bytes=0-1\r\nIf-Range: {}\r\n",
+            identity
+        );
+        let resp = r.process(&get(&fields)).await;
+        assert_eq!(Status::PartialContent, resp.status);
+        assert_eq!(Some(identity), etag(&resp));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,588 @@
+// Directory listings
+//
+// GET /files/docs/?format=json&sort=size&order=desc&page=2&per_page=50
+//
+// `format` overrides Accept, the rest defaults to sort=name&order=asc&page=1
+// with 100 entries per page.
+
+use std::{
+    cmp::Ordering,
+    fmt::Write,
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::sandbox::{self, SymlinkPolicy};
+use crate::{
+    date::format_http_date,
+    request::{percent_encode, Query},
+    response::ContentType,
+};
+
+const DEFAULT_PER_PAGE: usize = 100;
+const MAX_PER_PAGE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ListingError {
+    #[error("invalid value for the {0:?} parameter")]
+    InvalidParam(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Json,
+}
+
+impl Format {
+    /// Every format in order of preference.
+    pub const ALL: [Format; 2] = [Format::Html, Format::Json];
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Html => "text/html",
+            Format::Json => "application/json",
+        }
+    }
+
+    pub fn from_media_type(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.media_type() == value)
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            Format::Html => ContentType::TEXT_HTML.with_charset("utf-8"),
+            Format::Json => ContentType::JSON,
+        }
+    }
+
+    fn text(&self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Kind,
+}
+
+impl SortKey {
+    fn text(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Kind => "type",
+        }
+    }
+}
+
+/// How a listing is to be presented, as requested in the query string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// `None` if the format is to be negotiated.
+    pub format: Option<Format>,
+    pub sort: SortKey,
+    pub descending: bool,
+    /// Starts at 1.
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            format: None,
+            sort: SortKey::Name,
+            descending: false,
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_query(query: &Query) -> Result<Self, ListingError> {
+        let mut options = Options::default();
+
+        if let Some(format) = query.get("format") {
+            options.format = match format {
+                "html" => Some(Format::Html),
+                "json" => Some(Format::Json),
+                _ => return Err(ListingError::InvalidParam("format")),
+            };
+        }
+        if let Some(sort) = query.get("sort") {
+            options.sort = match sort {
+                "name" => SortKey::Name,
+                "size" => SortKey::Size,
+                "modified" => SortKey::Modified,
+                "type" => SortKey::Kind,
+                _ => return Err(ListingError::InvalidParam("sort")),
+            };
+        }
+        if let Some(order) = query.get("order") {
+            options.descending = match order {
+                "asc" => false,
+                "desc" => true,
+                _ => return Err(ListingError::InvalidParam("order")),
+            };
+        }
+
+        let number = |name: &'static str, max: usize| match query.get(name) {
+            None => Ok(None),
+            Some(value) => match value.parse::<usize>() {
+                Ok(n) if (1..=max).contains(&n) => Ok(Some(n)),
+                _ => Err(ListingError::InvalidParam(name)),
+            },
+        };
+        if let Some(page) = number("page", usize::MAX)? {
+            options.page = page;
+        }
+        if let Some(per_page) = number("per_page", MAX_PER_PAGE)? {
+            options.per_page = per_page;
+        }
+
+        Ok(options)
+    }
+
+    /// The query string for another page of the same listing.
+    fn page_query(&self, page: usize, format: Format) -> String {
+        format!(
+            "?format={}&sort={}&order={}&page={}&per_page={}",
+            format.text(),
+            self.sort.text(),
+            if self.descending { "desc" } else { "asc" },
+            page,
+            self.per_page
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Directory,
+    File,
+    Other,
+}
+
+impl Kind {
+    fn text(&self) -> &'static str {
+        match self {
+            Kind::Directory => "directory",
+            Kind::File => "file",
+            Kind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub kind: Kind,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// The entries of a directory below `/files`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    /// The path segments of the directory below `/files`, empty for the
+    /// configured directory itself.
+    pub dir: Vec<String>,
+    pub entries: Vec<Entry>,
+}
+
+impl Listing {
+    /// Reads the entries of `path`, names that aren't valid utf8 can't be
+    /// requested and are left out, as are entries that vanish meanwhile.
+    ///
+    /// Symlinks are listed as what they point to, if `policy` allows to
+    /// follow them, otherwise they are left out as well.
+    pub async fn read(
+        path: &Path,
+        dir: Vec<String>,
+        root: &Path,
+        policy: SymlinkPolicy,
+    ) -> io::Result<Self> {
+        let mut entries = vec![];
+        let mut read_dir = tokio::fs::read_dir(path).await?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            // a link that can't be resolved, e.g. a loop, is left out just
+            // like any other entry that can't be read
+            if file_type.is_symlink()
+                && !matches!(
+                    sandbox::follows(root, &entry.path(), policy).await,
+                    Ok(true)
+                )
+            {
+                continue;
+            }
+            let Ok(metadata) = tokio::fs::metadata(entry.path()).await else {
+                continue;
+            };
+
+            let kind = match metadata.file_type() {
+                t if t.is_dir() => Kind::Directory,
+                t if t.is_file() => Kind::File,
+                _ => Kind::Other,
+            };
+            entries.push(Entry {
+                name,
+                kind,
+                size: if kind == Kind::File {
+                    metadata.len()
+                } else {
+                    0
+                },
+                modified: metadata.modified().ok(),
+            });
+        }
+
+        Ok(Self { dir, entries })
+    }
+
+    /// Sorts the entries and renders the requested page of them.
+    pub fn render(mut self, options: &Options, format: Format) -> String {
+        self.entries.sort_by(|a, b| {
+            let ord = match options.sort {
+                SortKey::Name => Ordering::Equal,
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Modified => a.modified.cmp(&b.modified),
+                SortKey::Kind => a.kind.cmp(&b.kind),
+            };
+            let ord = ord.then_with(|| a.name.cmp(&b.name));
+            if options.descending {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+
+        // pages past the end are empty rather than an error
+        let start = options.per_page.saturating_mul(options.page - 1);
+        let page = Page {
+            listing: &self,
+            options,
+            entries: self
+                .entries
+                .iter()
+                .skip(start)
+                .take(options.per_page)
+                .collect(),
+        };
+
+        match format {
+            Format::Html => page.html(),
+            Format::Json => page.json(),
+        }
+    }
+
+    /// The absolute path of a directory below `/files`, ending in `/`.
+    fn path(dir: &[String]) -> String {
+        let mut path = "/files/".to_string();
+        for segment in dir {
+            path.push_str(&percent_encode(segment));
+            path.push('/');
+        }
+        path
+    }
+}
+
+struct Page<'a> {
+    listing: &'a Listing,
+    options: &'a Options,
+    entries: Vec<&'a Entry>,
+}
+
+impl Page<'_> {
+    fn total(&self) -> usize {
+        self.listing.entries.len()
+    }
+
+    fn has_next(&self) -> bool {
+        self.options.page.saturating_mul(self.options.per_page) < self.total()
+    }
+
+    fn json(&self) -> String {
+        let mut json = String::new();
+
+        let display = format!("/files/{}", self.listing.dir.join("/"));
+        let _ = write!(
+            json,
+            "{{\"path\":{},\"page\":{},\"per_page\":{},\"total\":{},\"entries\":[",
+            json_string(display.trim_end_matches('/')),
+            self.options.page,
+            self.options.per_page,
+            self.total()
+        );
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let modified = entry
+                .modified
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map_or("null".to_string(), |d| d.as_secs().to_string());
+
+            let _ = write!(
+                json,
+                "{{\"name\":{},\"type\":\"{}\",\"size\":{},\"modified\":{}}}",
+                json_string(&entry.name),
+                entry.kind.text(),
+                entry.size,
+                modified
+            );
+        }
+
+        json.push_str("]}\n");
+        json
+    }
+
+    fn html(&self) -> String {
+        let path = Listing::path(&self.listing.dir);
+        let title = html_escape(&format!("Index of /files/{}", self.listing.dir.join("/")));
+
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n<table>\n<tr><th>Name</th><th>Type</th><th>Size</th><th>Modified</th></tr>\n",
+            title
+        );
+
+        // links are absolute, the request target may lack the trailing `/`
+        if let Some((_, parent)) = self.listing.dir.split_last() {
+            let _ = writeln!(
+                html,
+                "<tr><td><a href=\"{}\">../</a></td><td>directory</td><td></td><td></td></tr>",
+                Listing::path(parent)
+            );
+        }
+
+        for entry in &self.entries {
+            let mut href = format!("{}{}", path, percent_encode(&entry.name));
+            let mut name = html_escape(&entry.name);
+            if entry.kind == Kind::Directory {
+                href.push('/');
+                name.push('/');
+            }
+
+            let _ = writeln!(
+                html,
+                "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                href,
+                name,
+                entry.kind.text(),
+                entry.size,
+                entry.modified.map(format_http_date).unwrap_or_default()
+            );
+        }
+        html.push_str("</table>\n");
+
+        let prev = (self.options.page > 1).then(|| self.options.page - 1);
+        let next = self.has_next().then(|| self.options.page + 1);
+        if prev.is_some() || next.is_some() {
+            html.push_str("<p>");
+            for (page, label) in [(prev, "previous"), (next, "next")] {
+                if let Some(page) = page {
+                    let query = self.options.page_query(page, Format::Html);
+                    let _ = write!(html, "<a href=\"{}\">{}</a> ", html_escape(&query), label);
+                }
+            }
+            html.push_str("</p>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn listing() -> Listing {
+        let entry = |name: &str, kind, size, secs| Entry {
+            name: name.to_string(),
+            kind,
+            size,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(secs)),
+        };
+
+        Listing {
+            dir: vec!["my docs".to_string()],
+            entries: vec![
+                entry("b.txt", Kind::File, 30, 3),
+                entry("a \"<q>\".txt", Kind::File, 10, 1),
+                entry("sub", Kind::Directory, 0, 2),
+            ],
+        }
+    }
+
+    fn query(value: &str) -> Query {
+        Query::parse(value).expect("valid query")
+    }
+
+    #[test]
+    fn parse_options() {
+        assert_eq!(Options::default(), Options::from_query(&query("")).unwrap());
+        assert_eq!(
+            Options {
+                format: Some(Format::Json),
+                sort: SortKey::Modified,
+                descending: true,
+                page: 3,
+                per_page: 20,
+            },
+            Options::from_query(&query(
+                "format=json&sort=modified&order=desc&page=3&per_page=20"
+            ))
+            .unwrap()
+        );
+
+        let all = [
+            ("format=xml", "format"),
+            ("sort=owner", "sort"),
+            ("order=up", "order"),
+            ("page=0", "page"),
+            ("page=-1", "page"),
+            ("per_page=1001", "per_page"),
+        ];
+        for (input, param) in all {
+            assert_eq!(
+                Err(ListingError::InvalidParam(param)),
+                Options::from_query(&query(input)),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn render_json() {
+        let options = Options {
+            sort: SortKey::Size,
+            descending: true,
+            per_page: 2,
+            ..Default::default()
+        };
+        let exp = concat!(
+            "{\"path\":\"/files/my docs\",\"page\":1,\"per_page\":2,\"total\":3,\"entries\":[",
+            "{\"name\":\"b.txt\",\"type\":\"file\",\"size\":30,\"modified\":3},",
+            "{\"name\":\"a \\\"<q>\\\".txt\",\"type\":\"file\",\"size\":10,\"modified\":1}",
+            "]}\n"
+        );
+        assert_eq!(exp, listing().render(&options, Format::Json));
+
+        let options = Options { page: 2, ..options };
+        let exp = concat!(
+            "{\"path\":\"/files/my docs\",\"page\":2,\"per_page\":2,\"total\":3,\"entries\":[",
+            "{\"name\":\"sub\",\"type\":\"directory\",\"size\":0,\"modified\":2}",
+            "]}\n"
+        );
+        assert_eq!(exp, listing().render(&options, Format::Json));
+    }
+
+    #[test]
+    fn render_html() {
+        let options = Options {
+            sort: SortKey::Kind,
+            per_page: 2,
+            ..Default::default()
+        };
+        let html = listing().render(&options, Format::Html);
+
+        assert!(html.contains("<title>Index of /files/my docs</title>"));
+        assert!(html.contains("<a href=\"/files/\">../</a>"));
+        let rows: Vec<_> = html
+            .lines()
+            .filter(|l| l.contains("/files/my%20docs/"))
+            .collect();
+        assert_eq!(
+            vec![
+                "<tr><td><a href=\"/files/my%20docs/sub/\">sub/</a></td><td>directory</td><td>0</td><td>Thu, 01 Jan 1970 00:00:02 GMT</td></tr>",
+                "<tr><td><a href=\"/files/my%20docs/a%20%22%3Cq%3E%22.txt\">a &quot;&lt;q&gt;&quot;.txt</a></td><td>file</td><td>10</td><td>Thu, 01 Jan 1970 00:00:01 GMT</td></tr>",
+            ],
+            rows
+        );
+        assert!(html.contains(
+            "<a href=\"?format=html&amp;sort=type&amp;order=asc&amp;page=2&amp;per_page=2\">next</a>"
+        ));
+        assert!(!html.contains("previous"));
+    }
+
+    #[tokio::test]
+    async fn read_skips_broken_links() {
+        let root = std::env::temp_dir().join(format!("listing-links-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(root.join("loop-b"), root.join("loop-a")).unwrap();
+        std::os::unix::fs::symlink(root.join("loop-a"), root.join("loop-b")).unwrap();
+
+        let listing = Listing::read(&root, vec![], &root, SymlinkPolicy::Within)
+            .await
+            .expect("readable directory");
+        let names: Vec<_> = listing.entries.iter().map(|e| &e.name[..]).collect();
+        assert_eq!(vec!["a.txt"], names);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,90 @@
+// Serializing changes to the same file
+//
+// PUT If-Match: "a" -> lock, check "a", write, unlock
+// PUT If-Match: "a" -> lock (waits), check "a" fails -> 412
+//
+// A precondition is only worth something if the file can't change between
+// checking it and acting on it.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// A lock for every path that is currently being changed.
+#[derive(Debug, Default)]
+pub struct PathLocks {
+    locks: Mutex<HashMap<PathBuf, Arc<AsyncMutex<()>>>>,
+}
+
+impl PathLocks {
+    /// Waits until nobody else holds the lock for `path`, it is held until
+    /// the guard is dropped.
+    pub async fn lock(&self, path: &Path) -> PathGuard<'_> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone();
+
+        PathGuard {
+            locks: self,
+            path: path.to_path_buf(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+pub struct PathGuard<'a> {
+    locks: &'a PathLocks,
+    path: PathBuf,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for PathGuard<'_> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+
+        // the lock is forgotten once nobody is waiting for it anymore, so
+        // that the map doesn't grow with every path ever changed
+        let mut locks = self
+            .locks
+            .locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if locks
+            .get(&self.path)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn lock_paths() {
+        let locks = PathLocks::default();
+        let wait = Duration::from_millis(10);
+
+        let a = locks.lock(Path::new("a")).await;
+        assert!(timeout(wait, locks.lock(Path::new("b"))).await.is_ok());
+        assert!(timeout(wait, locks.lock(Path::new("a"))).await.is_err());
+
+        drop(a);
+        assert!(timeout(wait, locks.lock(Path::new("a"))).await.is_ok());
+        assert_eq!(0, locks.locks.lock().unwrap().len());
+    }
+}
@@ -0,0 +1,258 @@
+// Media types of served files
+//
+// The type is looked up by extension, first in the override file, then in
+// the built-in table. Files with an unknown extension are sniffed if enabled,
+// otherwise they are sent as application/octet-stream.
+//
+// # mime.types
+// text/markdown                  md markdown
+// application/vnd.example+json   example
+
+use std::{collections::HashMap, path::Path};
+
+use crate::response::ContentType;
+
+/// How many bytes at the start of a file are looked at to guess its type.
+pub const SNIFF_LEN: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MimeError {
+    #[error("line {0}: invalid media type {1:?}")]
+    InvalidType(usize, String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MimeTypes {
+    /// Lowercase extensions mapped to their type.
+    overrides: HashMap<String, ContentType>,
+    sniff: bool,
+}
+
+impl MimeTypes {
+    /// Only the built-in table, `sniff` enables guessing the type of files with
+    /// unknown extensions from their content.
+    pub fn new(sniff: bool) -> Self {
+        Self {
+            overrides: HashMap::new(),
+            sniff,
+        }
+    }
+
+    /// Adds the extensions of a `mime.types` file, i.e. lines of a media type
+    /// followed by its extensions, `#` starting a comment.
+    pub fn with_overrides(mut self, text: &str) -> Result<Self, MimeError> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(essence) = fields.next() else {
+                continue;
+            };
+
+            let content_type = ContentType::new(essence)
+                .ok_or_else(|| MimeError::InvalidType(i + 1, essence.to_string()))?;
+            for ext in fields {
+                let ext = ext.trim_start_matches('.').to_lowercase();
+                self.overrides.insert(ext, content_type.clone());
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// The type of a file going by its extension alone.
+    pub fn lookup(&self, path: &Path) -> Option<ContentType> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+
+        match self.overrides.get(&ext) {
+            Some(content_type) => Some(content_type.clone()),
+            None => builtin(&ext).map(ContentType::from_static),
+        }
+    }
+
+    /// The type to send the file at `path` with, `content` only needs to hold
+    /// its first `SNIFF_LEN` bytes. Text is labelled as utf8 if it starts out
+    /// as such.
+    pub fn content_type(&self, path: &Path, content: &[u8]) -> ContentType {
+        let content_type = self
+            .lookup(path)
+            .or_else(|| self.sniff.then(|| sniff(content)).flatten())
+            .unwrap_or(ContentType::OCTET_STREAM);
+
+        if content_type.essence().starts_with("text/") && looks_like_utf8(content) {
+            content_type.with_charset("utf-8")
+        } else {
+            content_type
+        }
+    }
+}
+
+fn builtin(ext: &str) -> Option<&'static str> {
+    let essence = match ext {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "txt" | "text" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "json" | "map" => "application/json",
+        "xml" => "application/xml",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/vnd.microsoft.icon",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => return None,
+    };
+    Some(essence)
+}
+
+/// Guesses the type from well known signatures, falls back to text/plain
+/// for anything that looks like text.
+fn sniff(content: &[u8]) -> Option<ContentType> {
+    let head = &content[..content.len().min(SNIFF_LEN)];
+
+    let signatures: [(&[u8], &'static str); 9] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\0asm", "application/wasm"),
+        (b"<?xml", "application/xml"),
+    ];
+    if let Some((_, essence)) = signatures.iter().find(|(sig, _)| head.starts_with(sig)) {
+        return Some(ContentType::from_static(essence));
+    }
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        return Some(ContentType::from_static("image/webp"));
+    }
+
+    let start = head
+        .iter()
+        .position(|c| !c.is_ascii_whitespace())
+        .unwrap_or(head.len());
+    let markup = head[start..].to_ascii_lowercase();
+    if markup.starts_with(b"<!doctype html") || markup.starts_with(b"<html") {
+        return Some(ContentType::TEXT_HTML);
+    }
+
+    // binary formats are full of control characters, text hardly has any
+    let is_text = !head
+        .iter()
+        .any(|c| c.is_ascii_control() && !b"\t\n\x0c\r\x1b".contains(c));
+    (is_text && looks_like_utf8(head)).then_some(ContentType::TEXT_PLAIN)
+}
+
+/// Whether the start of `content` is valid utf8, allowing for a character
+/// cut off at the end of what is looked at.
+fn looks_like_utf8(content: &[u8]) -> bool {
+    let head = &content[..content.len().min(SNIFF_LEN)];
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none() && head.len() == SNIFF_LEN,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn lookup_extensions() {
+        let mime = MimeTypes::new(false)
+            .with_overrides(
+                "# comment\n\ntext/x-readme readme TXT # trailing\napplication/vnd.a+json .a\n",
+            )
+            .expect("valid overrides");
+
+        let all = [
+            ("index.HTML", Some("text/html")),
+            ("notes.txt", Some("text/x-readme")),
+            ("README", None),
+            ("x.readme", Some("text/x-readme")),
+            ("data.a", Some("application/vnd.a+json")),
+            ("archive.tar.gz", Some("application/gzip")),
+            ("unknown.xyz", None),
+        ];
+        for (path, exp) in all {
+            let content_type = mime.lookup(Path::new(path));
+            assert_eq!(
+                exp,
+                content_type.as_ref().map(ContentType::essence),
+                "{:?}",
+                path
+            );
+        }
+
+        assert_eq!(
+            Some(MimeError::InvalidType(2, "text".to_string())),
+            MimeTypes::new(false)
+                .with_overrides("text/plain txt\ntext md\n")
+                .err()
+        );
+    }
+
+    #[test]
+    fn content_types() {
+        let mime = MimeTypes::new(false);
+        let all: [(&str, &[u8], &str); 4] = [
+            ("a.html", b"<p>caf\xc3\xa9</p>", "text/html; charset=utf-8"),
+            ("a.txt", b"caf\xe9", "text/plain"),
+            ("a.json", b"{}", "application/json"),
+            ("a", b"plain text", "application/octet-stream"),
+        ];
+        for (path, content, exp) in all {
+            assert_eq!(
+                exp,
+                mime.content_type(Path::new(path), content).text(),
+                "{:?}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn sniff_content() {
+        let mime = MimeTypes::new(true);
+        let all: [(&[u8], &str); 7] = [
+            (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", "image/png"),
+            (b"RIFF\0\0\0\0WEBPVP8 ", "image/webp"),
+            (b"%PDF-1.7\n", "application/pdf"),
+            (b"\n  <!DOCTYPE HTML><html>", "text/html; charset=utf-8"),
+            (b"just some text\n", "text/plain; charset=utf-8"),
+            (b"\0\x01\x02\x03", "application/octet-stream"),
+            (b"caf\xe9", "application/octet-stream"),
+        ];
+        for (content, exp) in all {
+            assert_eq!(
+                exp,
+                mime.content_type(Path::new("upload"), content).text(),
+                "{:?}",
+                content
+            );
+        }
+
+        // the extension always wins
+        let content_type = mime.content_type(Path::new("a.css"), b"%PDF-1.7\n");
+        assert_eq!("text/css; charset=utf-8", content_type.text());
+    }
+}
@@ -0,0 +1,200 @@
+// Resolving request paths beneath the served directory
+//
+// GET /files/docs/2024/report.txt   -> <directory>/docs/2024/report.txt
+// GET /files/..%2F..%2Fetc%2Fpasswd -> 400, a segment can't hold `/`
+//
+// Dot segments are already resolved and encoded separators rejected when the
+// url is parsed, so mostly symlinks are left to lead outside of the directory.
+
+use std::{
+    io::{self, ErrorKind},
+    path::{Component, Path, PathBuf},
+};
+
+use tokio::fs::{canonicalize, symlink_metadata};
+
+/// Which symlinks beneath the served directory are followed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SymlinkPolicy {
+    /// Never follow symlinks
+    Deny,
+    /// Follow symlinks as long as they point beneath the directory
+    #[default]
+    Within,
+    /// Follow every symlink, wherever it points
+    Follow,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error("the path leads outside of the directory")]
+    Escape,
+    #[error("the path contains a symlink that isn't followed")]
+    Symlink,
+    #[error("{0:?} is not a directory")]
+    NotADirectory(PathBuf),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Joins `segments` onto `root`, making sure the result stays beneath it.
+///
+/// The path doesn't need to exist, only the existing part of it is checked
+/// for symlinks, which are handled according to `policy`.
+pub async fn resolve(
+    root: &Path,
+    segments: &[String],
+    policy: SymlinkPolicy,
+) -> Result<PathBuf, SandboxError> {
+    let mut path = root.to_path_buf();
+    let mut exists = true;
+
+    for (i, segment) in segments.iter().enumerate() {
+        // a segment has to be a single plain name, e.g. not `a/b`, `/etc` or
+        // `C:` once decoded
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if *name == segment[..] => path.push(name),
+            _ => return Err(SandboxError::Escape),
+        }
+
+        if !exists {
+            continue;
+        }
+        let metadata = match symlink_metadata(&path).await {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                exists = false;
+                continue;
+            }
+            res => res?,
+        };
+
+        let is_dir = match metadata.is_symlink() {
+            true if follows(root, &path, policy).await? => {
+                match tokio::fs::metadata(&path).await {
+                    Ok(metadata) => metadata.is_dir(),
+                    // a dangling link, which can't be created through
+                    Err(err) if err.kind() == ErrorKind::NotFound => {
+                        return Err(SandboxError::Symlink)
+                    }
+                    Err(err) => Err(err)?,
+                }
+            }
+            true => return Err(SandboxError::Symlink),
+            false => metadata.is_dir(),
+        };
+
+        if !is_dir && i + 1 < segments.len() {
+            return Err(SandboxError::NotADirectory(path));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Whether the symlink at `link` may be followed according to `policy`.
+pub async fn follows(root: &Path, link: &Path, policy: SymlinkPolicy) -> io::Result<bool> {
+    match policy {
+        SymlinkPolicy::Deny => Ok(false),
+        SymlinkPolicy::Follow => Ok(true),
+        SymlinkPolicy::Within => {
+            let target = match canonicalize(link).await {
+                Ok(target) => target,
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+                Err(err) => return Err(err),
+            };
+            Ok(target.starts_with(canonicalize(root).await?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A fresh directory with `inside/file.txt`, a file `outside.txt` next to
+    /// it and symlinks to both.
+    fn setup(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("sandbox-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let root = base.join("root");
+
+        std::fs::create_dir_all(root.join("inside")).unwrap();
+        std::fs::write(root.join("inside/file.txt"), "in").unwrap();
+        std::fs::write(base.join("outside.txt"), "out").unwrap();
+        std::os::unix::fs::symlink(root.join("inside"), root.join("link-in")).unwrap();
+        std::os::unix::fs::symlink(base.join("outside.txt"), root.join("link-out")).unwrap();
+
+        root
+    }
+
+    fn segments(path: &str) -> Vec<String> {
+        path.split('/').map(str::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn resolve_paths() {
+        let root = setup("resolve");
+
+        let path = resolve(&root, &segments("inside/file.txt"), SymlinkPolicy::Deny)
+            .await
+            .unwrap();
+        assert_eq!(root.join("inside/file.txt"), path);
+
+        // missing parts are fine, they'll be created
+        let path = resolve(&root, &segments("a/b/c.txt"), SymlinkPolicy::Deny)
+            .await
+            .unwrap();
+        assert_eq!(root.join("a/b/c.txt"), path);
+
+        for input in ["../x", "a/../x", "/etc/passwd", "", ".", "a\\..\\b/c"] {
+            let res = resolve(&root, &[input.to_string()], SymlinkPolicy::Follow).await;
+            assert!(
+                matches!(res, Err(SandboxError::Escape)),
+                "{:?}: {:?}",
+                input,
+                res
+            );
+        }
+
+        let res = resolve(&root, &segments("inside/file.txt/x"), SymlinkPolicy::Deny).await;
+        assert!(
+            matches!(res, Err(SandboxError::NotADirectory(_))),
+            "{:?}",
+            res
+        );
+
+        let _ = std::fs::remove_dir_all(root.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn symlink_policies() {
+        let root = setup("symlinks");
+
+        let all = [
+            ("link-in/file.txt", SymlinkPolicy::Deny, false),
+            ("link-in/file.txt", SymlinkPolicy::Within, true),
+            ("link-in/file.txt", SymlinkPolicy::Follow, true),
+            ("link-out", SymlinkPolicy::Deny, false),
+            ("link-out", SymlinkPolicy::Within, false),
+            ("link-out", SymlinkPolicy::Follow, true),
+        ];
+
+        for (input, policy, exp) in all {
+            let res = resolve(&root, &segments(input), policy).await;
+            match exp {
+                true => assert_eq!(root.join(input), res.unwrap()),
+                false => assert!(
+                    matches!(res, Err(SandboxError::Symlink)),
+                    "{:?} {:?}: {:?}",
+                    input,
+                    policy,
+                    res
+                ),
+            }
+        }
+
+        let _ = std::fs::remove_dir_all(root.parent().unwrap());
+    }
+}
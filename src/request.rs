@@ -13,20 +13,73 @@
 //
 // // Request body (empty)
 
-use std::{collections::HashMap, fmt::Debug};
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub mod chunked;
+pub mod conditional;
+mod cookie;
+pub mod form;
+mod headers;
+mod media_type;
+pub mod multipart;
+mod parser;
+mod query;
+pub mod range;
+mod url;
+
+pub use conditional::{EntityTag, Precondition, Validators};
+pub use cookie::CookieJar;
+pub use headers::{HeaderMap, HeaderValue};
+pub use media_type::MediaType;
+pub use parser::{Parsed, Parser};
+pub use query::Query;
+pub use url::{percent_encode, Authority, TargetForm, Url, UrlError};
+
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Method {
     Get,
+    Head,
     Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+    Trace,
+    Connect,
+    /// Any other method token, kept as sent by the client.
+    Extension(String),
+}
+
+impl Method {
+    pub fn text(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Connect => "CONNECT",
+            Method::Extension(method) => method,
+        }
+    }
 }
 
 impl From<&str> for Method {
     fn from(value: &str) -> Self {
-        match value {
+        match &value.to_uppercase()[..] {
             "GET" => Self::Get,
+            "HEAD" => Self::Head,
             "POST" => Self::Post,
-            _ => unimplemented!("This method type has not been implemented"),
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "PATCH" => Self::Patch,
+            "OPTIONS" => Self::Options,
+            "TRACE" => Self::Trace,
+            "CONNECT" => Self::Connect,
+            _ => Self::Extension(value.to_string()),
         }
     }
 }
@@ -34,13 +87,91 @@ impl From<&str> for Method {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Encoding {
     Gzip,
+    Deflate,
+    Identity,
 }
 
 impl Encoding {
     pub fn text(&self) -> &str {
         match self {
             Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Identity => "identity",
+        }
+    }
+
+    /// Picks the coding to use for a response given the `Accept-Encoding`
+    /// field (RFC 9110 section 12.5.3), `None` if none of them is acceptable.
+    ///
+    /// Of the codings the client rates equally, ours are preferred in the
+    /// order gzip, deflate, identity, no matter the order they were listed in.
+    pub fn negotiate(accept: &str) -> Option<Self> {
+        let mut wildcard = None;
+        let mut weights = vec![];
+
+        for coding in accept.split(',') {
+            let mut params = coding.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            if name.is_empty() {
+                continue;
+            }
+
+            let mut weight = Some(1000);
+            for param in params {
+                if let Some((key, value)) = param.split_once('=') {
+                    if key.trim().eq_ignore_ascii_case("q") {
+                        weight = parse_qvalue(value.trim());
+                    }
+                }
+            }
+            // elements with an invalid weight are ignored
+            let Some(weight) = weight else {
+                continue;
+            };
+
+            if name == "*" {
+                wildcard = Some(weight);
+            } else if let Ok(enc) = Encoding::try_from(&name.to_lowercase()[..]) {
+                weights.push((enc, weight));
+            }
         }
+
+        let weight = |enc: Encoding| {
+            let explicit = weights.iter().find(|(e, _)| *e == enc).map(|(_, w)| *w);
+            match (explicit, wildcard, enc) {
+                (Some(w), _, _) | (None, Some(w), _) => w,
+                // identity is always acceptable unless excluded explicitly
+                (None, None, Encoding::Identity) => 1,
+                (None, None, _) => 0,
+            }
+        };
+
+        [Encoding::Gzip, Encoding::Deflate, Encoding::Identity]
+            .into_iter()
+            .map(|enc| (enc, weight(enc)))
+            .filter(|(_, w)| *w > 0)
+            .fold(None, |best: Option<(Encoding, u16)>, (enc, w)| match best {
+                Some((_, bw)) if bw >= w => best,
+                _ => Some((enc, w)),
+            })
+            .map(|(enc, _)| enc)
+    }
+}
+
+/// qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] ), returned
+/// in thousandths.
+fn parse_qvalue(value: &str) -> Option<u16> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+
+    if frac.len() > 3 || !frac.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let frac = format!("{:0<3}", frac).parse::<u16>().ok()?;
+    match int {
+        "0" => Some(frac),
+        "1" if frac == 0 => Some(1000),
+        _ => None,
     }
 }
 
@@ -49,20 +180,24 @@ impl TryFrom<&str> for Encoding {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "gzip" => Ok(Encoding::Gzip),
+            "gzip" | "x-gzip" => Ok(Encoding::Gzip),
+            "deflate" => Ok(Encoding::Deflate),
+            "identity" => Ok(Encoding::Identity),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Version {
+    Http10,
     Http11,
 }
 
 impl Debug for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Http10 => write!(f, "HTTP/1.0"),
             Self::Http11 => write!(f, "HTTP/1.1"),
         }
     }
@@ -71,75 +206,298 @@ impl Debug for Version {
 impl Version {
     pub fn text(&self) -> &str {
         match self {
+            Version::Http10 => "HTTP/1.0",
             Version::Http11 => "HTTP/1.1",
         }
     }
 }
 
-impl From<&str> for Version {
-    fn from(value: &str) -> Self {
+impl TryFrom<&str> for Version {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "HTTP/1.1" => Self::Http11,
-            _ => unimplemented!("This version type has not been implemented"),
+            "HTTP/1.0" => Ok(Self::Http10),
+            "HTTP/1.1" => Ok(Self::Http11),
+            _ => Err(ParseError::UnsupportedVersion(value.to_string())),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Url {
-    pub sections: Vec<String>,
-    pub query: Option<String>,
+pub struct Header {
+    pub method: Method,
+    pub url: Url,
+    pub version: Version,
+    /// The negotiated content coding, `None` if the client didn't send
+    /// `Accept-Encoding` or if none of our codings is acceptable to it.
+    pub accept_encoding: Option<Encoding>,
+    pub headers: HeaderMap,
 }
 
-impl From<&str> for Url {
-    fn from(value: &str) -> Self {
-        let (uri, query) = match value.split_once('?') {
-            None => (value, None),
-            Some((uri, query)) => (uri, Some(query.to_string())),
-        };
-
-        let mut parts = vec![];
-        if uri == "/" {
-            parts.push("/".to_string());
-            return Self {
-                sections: parts,
-                query,
+impl Header {
+    /// The announced length of the body, `None` if the client didn't send one.
+    pub fn content_length(&self) -> Result<Option<usize>, ParseError> {
+        let mut length = None;
+
+        // repeated fields are only acceptable if they all agree
+        for v in self.headers.get_all("content-length") {
+            // only plain digits, `parse` would also accept a leading `+`
+            let len = match v
+                .to_str()
+                .filter(|v| !v.is_empty() && v.bytes().all(|c| c.is_ascii_digit()))
+                .and_then(|v| v.parse().ok())
+            {
+                Some(len) => len,
+                None => return Err(ParseError::InvalidContentLength),
             };
+
+            match length {
+                Some(prev) if prev != len => return Err(ParseError::InvalidContentLength),
+                _ => length = Some(len),
+            }
         }
-        for sections in uri.split('/') {
-            // this is root
-            if sections.is_empty() {
-                continue;
+
+        Ok(length)
+    }
+
+    /// The authority the request is directed at (RFC 9112 section 3.3), from
+    /// the request target if it has one, otherwise from Host.
+    ///
+    /// HTTP/1.1 requests need exactly one valid Host field, which has to agree
+    /// with the authority of an absolute-form target.
+    pub fn authority(&self) -> Result<Option<Authority>, ParseError> {
+        let mut hosts = self.headers.get_all("host");
+
+        let host = match (hosts.next(), hosts.next()) {
+            (None, _) if self.version == Version::Http11 => {
+                return Err(ParseError::BadHost("missing host"))
             }
-            parts.push(sections.to_string());
+            (None, _) => None,
+            (Some(host), None) => Some(
+                host.to_str()
+                    .and_then(|h| h.parse::<Authority>().ok())
+                    .ok_or(ParseError::BadHost("invalid host"))?,
+            ),
+            (Some(_), Some(_)) => return Err(ParseError::BadHost("duplicate host")),
+        };
+
+        let Some(authority) = &self.url.authority else {
+            // an empty Host is sent for targets without an authority
+            return Ok(host.filter(|h| !h.host.is_empty()));
+        };
+
+        let default_port = match self.url.scheme.as_deref() {
+            Some("https") => 443,
+            _ => 80,
+        };
+        if host.is_some_and(|h| !h.matches(authority, default_port)) {
+            return Err(ParseError::BadHost("host doesn't match the request target"));
         }
 
-        Self {
-            sections: parts,
-            query,
+        Ok(Some(authority.clone()))
+    }
+
+    /// The byte ranges asked for, `None` if there is no Range field or it has
+    /// to be ignored, e.g. because it is malformed or repeated.
+    pub fn range(&self) -> Option<Vec<range::RangeSpec>> {
+        let mut ranges = self.headers.get_all("range");
+
+        match (ranges.next(), ranges.next()) {
+            (Some(value), None) => value.to_str().and_then(range::parse),
+            _ => None,
         }
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Header {
-    pub method: Method,
-    pub url: Url,
-    pub version: Version,
-    pub accept_encoding: Option<Encoding>,
-    pub headers: HashMap<String, String>,
+    /// How the request is to be answered given its preconditions and the
+    /// validators of the current representation, `None` if there is none.
+    pub fn preconditions(&self, current: Option<&Validators>) -> Precondition {
+        conditional::evaluate(self, current)
+    }
+
+    /// Whether the Range field is to be honoured, i.e. there is no If-Range
+    /// or it matches the current representation.
+    pub fn if_range_matches(&self, validators: &Validators) -> bool {
+        conditional::if_range(self, validators)
+    }
+
+    /// Whether the connection should stay open after this request, HTTP/1.1
+    /// connections persist unless the client asks to close them, HTTP/1.0 ones
+    /// only if the client explicitly asks for keep-alive.
+    pub fn keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.headers
+                .get_all("connection")
+                .filter_map(HeaderValue::to_str)
+                .flat_map(|v| v.split(','))
+                .any(|opt| opt.trim().eq_ignore_ascii_case(option))
+        };
+
+        match self.version {
+            Version::Http10 => has_option("keep-alive"),
+            Version::Http11 => !has_option("close"),
+        }
+    }
+
+    /// Whether the client sent `Accept-Encoding`, but none of the codings we
+    /// support is acceptable to it.
+    pub fn no_acceptable_encoding(&self) -> bool {
+        self.accept_encoding.is_none() && self.headers.contains("accept-encoding")
+    }
+
+    /// The first of `offers` the client prefers according to `Accept`, with
+    /// no such field any media type is acceptable.
+    pub fn preferred_media_type<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        match self.headers.get_joined("accept") {
+            None => offers.first().copied(),
+            Some(accept) => media_type::negotiate(&accept, offers),
+        }
+    }
+
+    /// The media type of the body, `None` if it is missing or malformed.
+    pub fn content_type(&self) -> Option<MediaType> {
+        self.headers
+            .get_str("content-type")
+            .and_then(MediaType::parse)
+    }
+
+    /// Whether the body is sent using the chunked transfer coding, which has
+    /// to be the final coding applied.
+    pub fn is_chunked(&self) -> bool {
+        match self.headers.get_joined("transfer-encoding") {
+            None => false,
+            Some(v) => v
+                .rsplit(',')
+                .next()
+                .is_some_and(|enc| enc.trim().eq_ignore_ascii_case("chunked")),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Request {
     pub header: Header,
     pub body: Option<Vec<u8>>,
+    /// Fields sent after a chunked body.
+    pub trailers: HeaderMap,
+}
+
+impl Request {
+    /// The decoded query string of the request target, an error if it is
+    /// malformed.
+    pub fn query(&self) -> Result<Query, UrlError> {
+        self.header.url.query()
+    }
+
+    /// The cookies sent along with the request.
+    pub fn cookies(&self) -> CookieJar<'_> {
+        CookieJar::parse(&self.header.headers)
+    }
+
+    /// The parts of a `multipart/form-data` body.
+    pub fn multipart(&self) -> Result<Vec<multipart::Part<'_>>, multipart::MultipartError> {
+        let media_type = self
+            .header
+            .content_type()
+            .filter(|m| m.essence() == "multipart/form-data")
+            .ok_or(multipart::MultipartError::NotMultipart)?;
+
+        let boundary = media_type
+            .param("boundary")
+            .filter(|b| multipart::is_valid_boundary(b))
+            .ok_or(multipart::MultipartError::MissingBoundary)?;
+
+        multipart::parse(self.body.as_deref().unwrap_or_default(), boundary)
+    }
+
+    /// The fields of an `application/x-www-form-urlencoded` body, decoded
+    /// with the charset given in Content-Type or utf8 if there is none.
+    pub fn form(&self) -> Result<Query, form::FormError> {
+        let media_type = self
+            .header
+            .content_type()
+            .filter(|m| m.essence() == "application/x-www-form-urlencoded")
+            .ok_or(form::FormError::NotForm)?;
+
+        let charset = match media_type.param("charset") {
+            None => form::Charset::Utf8,
+            Some(label) => form::Charset::from_label(label)
+                .ok_or_else(|| form::FormError::UnsupportedCharset(label.to_string()))?,
+        };
+
+        form::parse(self.body.as_deref().unwrap_or_default(), charset)
+    }
+}
+
+/// Everything that can be wrong with a request sent by a client.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("malformed request line")]
+    BadRequestLine,
+    #[error("invalid request target: {0}")]
+    InvalidUrl(#[from] UrlError),
+    #[error("unsupported http version {0:?}")]
+    UnsupportedVersion(String),
+    #[error("malformed header field: {0}")]
+    BadHeader(&'static str),
+    #[error("bad host: {0}")]
+    BadHost(&'static str),
+    #[error("invalid or conflicting content length")]
+    InvalidContentLength,
+    #[error("malformed chunked body")]
+    InvalidChunkedBody,
+    #[error("the request ended before its body was complete")]
+    Incomplete,
+    #[error("request target is too long")]
+    UriTooLong,
+    #[error("header section is too large")]
+    HeadersTooLarge,
+    #[error("body is too large")]
+    BodyTooLarge,
+}
+
+/// Parses a request that was received completely with the default limits,
+/// the returned slice holds everything after it, e.g. pipelined requests.
+pub fn parse(buf: &[u8]) -> Result<(Request, &[u8]), ParseError> {
+    match Parser::default().feed(buf) {
+        Parsed::Done(request, consumed) => Ok((*request, &buf[consumed..])),
+        Parsed::NeedMore => Err(ParseError::Incomplete),
+        Parsed::Error(err) => Err(err),
+    }
 }
 
-pub fn parse(buf: &[u8]) -> anyhow::Result<(Request, &[u8])> {
-    match parsing::parse(buf) {
-        Ok((res, req)) => Ok((req, res)),
-        Err(err) => Err(anyhow::format_err!("{:?}", err)),
+/// Bounds on the size of a request, anything larger is rejected before it is
+/// read completely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Length of the request line without the line break.
+    pub max_request_line: usize,
+    /// Size of all header lines, including their line breaks.
+    pub max_header_bytes: usize,
+    pub max_headers: usize,
+    /// Size of the body as sent, i.e. including the chunked framing.
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_request_line: 8 * 1024,
+            max_header_bytes: 64 * 1024,
+            max_headers: 100,
+            max_body: 100 * 1024 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks the parts of the header that can only be judged once it is
+    /// parsed, i.e. the announced body size.
+    pub fn check_header(&self, header: &Header) -> Result<(), ParseError> {
+        if header.content_length()?.unwrap_or(0) > self.max_body {
+            return Err(ParseError::BodyTooLarge);
+        }
+        Ok(())
     }
 }
 
@@ -147,104 +505,106 @@ mod parsing {
     use super::*;
     use nom::{
         branch::alt,
-        bytes::complete::{tag_no_case, take_till, take_until},
+        bytes::complete::{tag_no_case, take_till, take_while1},
         character::complete::char,
-        error::{context, VerboseError},
-        multi::fold_many0,
-        sequence::{terminated, tuple},
+        error::{context, VerboseError, VerboseErrorKind},
+        sequence::tuple,
     };
 
     pub type Result<T, V> = nom::IResult<T, V, VerboseError<T>>;
 
-    pub fn parse(buf: &[u8]) -> Result<&[u8], Request> {
-        let (mut res, header) = parse_header(buf)?;
+    /// Maps the innermost context we know about to the part of the request
+    /// that couldn't be parsed.
+    pub fn to_parse_error(err: nom::Err<VerboseError<&[u8]>>) -> ParseError {
+        let errors = match err {
+            nom::Err::Incomplete(_) => return ParseError::BadRequestLine,
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.errors,
+        };
 
-        // get body
-        let body = if res.is_empty() {
-            None
-        } else {
-            let v = res.to_vec();
-            res = &[];
+        for (input, kind) in errors {
+            let VerboseErrorKind::Context(ctx) = kind else {
+                continue;
+            };
 
-            Some(v)
-        };
+            let until = |end: u8| {
+                let pos = input.iter().position(|&c| c == end).unwrap_or(input.len());
+                String::from_utf8_lossy(&input[..pos]).to_string()
+            };
 
-        Ok((res, Request { header, body }))
+            return match ctx {
+                "Method" | "request line" => ParseError::BadRequestLine,
+                "Version" => ParseError::UnsupportedVersion(until(b'\r')),
+                "url" => match until(b' ').parse::<Url>() {
+                    Err(err) => ParseError::InvalidUrl(err),
+                    Ok(_) => ParseError::BadRequestLine,
+                },
+                "header line" | "header name" => ParseError::BadHeader("malformed field line"),
+                ctx => ParseError::BadHeader(ctx),
+            };
+        }
+
+        ParseError::BadRequestLine
     }
 
-    fn parse_header(buf: &[u8]) -> Result<&[u8], Header> {
-        let header = tuple((
-            terminated(parse_request_line, parse_new_line),
-            parse_header_lines,
-        ));
+    /// field-line = field-name ":" OWS field-value OWS (RFC 9112 section 5)
+    pub(super) fn parse_header_line(buf: &[u8]) -> Result<&[u8], (String, HeaderValue)> {
+        let (res, key) = context("header name", take_while1(is_tchar))(buf)?;
 
-        let (buf, ((method, url, version), headers)) =
-            context("header", terminated(header, parse_new_line))(buf)?;
+        // whitespace between the name and the colon has to be rejected
+        if res.first().is_some_and(|&c| is_ows(c)) {
+            return Err(failure(res, "whitespace before colon"));
+        }
 
-        let mut accept_encoding = None;
+        let (res, (_, value)) = context(
+            "header line",
+            tuple((char(':'), take_till(|c| c == b'\r' || c == b'\n'))),
+        )(res)?;
 
-        if let Some(l) = headers.get("accept-encoding") {
-            for enc in l.split(',') {
-                if let Ok(enc) = enc.trim().try_into() {
-                    accept_encoding = Some(enc);
-                    break;
-                }
-            }
+        let value = trim_ows(value);
+        if !value.iter().all(|&c| is_field_char(c)) {
+            return Err(failure(value, "header value"));
         }
 
-        Ok((
-            buf,
-            Header {
-                method,
-                url,
-                version,
-                headers,
-                accept_encoding,
-            },
-        ))
-    }
-
-    fn parse_header_lines(buf: &[u8]) -> Result<&[u8], HashMap<String, String>> {
-        context(
-            "header lines",
-            fold_many0(
-                terminated(parse_header_line, parse_new_line),
-                HashMap::new,
-                |mut map: HashMap<_, _>, (k, v)| {
-                    map.insert(k, v);
-                    map
-                },
-            ),
-        )(buf)
-    }
-
-    fn parse_header_line(buf: &[u8]) -> Result<&[u8], (String, String)> {
-        let (res, (key, _, _, value)) = context(
-            "header line",
-            tuple((
-                take_till(is_colon),
-                char(':'),
-                char(' '),
-                take_till(|c| c == b'\r'),
-            )),
-        )(buf)?;
+        // only contains tchars, so this is plain ascii
+        let key = std::str::from_utf8(key)
+            .expect("unable to parse")
+            .to_string();
 
-        let to_string = |s| std::str::from_utf8(s).expect("unable to parse").to_string();
+        Ok((res, (key, value.into())))
+    }
 
-        Ok((res, (to_string(key).to_lowercase(), to_string(value))))
+    fn trim_ows(mut value: &[u8]) -> &[u8] {
+        while let [first, rest @ ..] = value {
+            if !is_ows(*first) {
+                break;
+            }
+            value = rest;
+        }
+        while let [rest @ .., last] = value {
+            if !is_ows(*last) {
+                break;
+            }
+            value = rest;
+        }
+        value
     }
 
-    fn parse_new_line(buf: &[u8]) -> Result<&[u8], &[u8]> {
-        let (res, _) = context("new line", tuple((char('\r'), char('\n'))))(buf)?;
+    fn is_ows(c: u8) -> bool {
+        c == b' ' || c == b'\t'
+    }
 
-        Ok((res, &buf[2..]))
+    /// VCHAR, obs-text or whitespace, anything but control characters.
+    fn is_field_char(c: u8) -> bool {
+        is_ows(c) || (c >= 0x21 && c != 0x7f)
     }
 
-    fn is_colon(c: u8) -> bool {
-        c == b':'
+    fn failure<'a>(input: &'a [u8], ctx: &'static str) -> nom::Err<VerboseError<&'a [u8]>> {
+        nom::Err::Failure(VerboseError {
+            errors: vec![(input, VerboseErrorKind::Context(ctx))],
+        })
     }
 
-    fn parse_request_line(buf: &[u8]) -> Result<&[u8], (Method, Url, Version)> {
+    pub(super) fn parse_request_line(buf: &[u8]) -> Result<&[u8], (Method, Url, Version)> {
         let (res, (method, _, url, _, version)) = context(
             "request line",
             tuple((parse_method, char(' '), parse_url, char(' '), parse_version)),
@@ -254,34 +614,45 @@ mod parsing {
     }
 
     fn parse_url(buf: &[u8]) -> Result<&[u8], Url> {
-        let (res, url) = take_until(" ")(buf)?;
-        Ok((res, std::str::from_utf8(url).expect("url not valid").into()))
-    }
+        let (res, url) = context("url", take_till(|c| c == b' ' || c == b'\r' || c == b'\n'))(buf)?;
 
-    fn parse_version(buf: &[u8]) -> Result<&[u8], Version> {
-        let (res, s) = context("Version", alt((tag_no_case("HTTP/1.1".as_bytes()),)))(buf)?;
-
-        let s = std::str::from_utf8(s)
-            .expect("unable to parse into utf8")
-            .to_uppercase();
+        let url = std::str::from_utf8(url)
+            .ok()
+            .and_then(|url| url.parse().ok())
+            .ok_or_else(|| failure(buf, "url"))?;
 
-        Ok((res, s[..].into()))
+        Ok((res, url))
     }
 
-    fn parse_method(buf: &[u8]) -> Result<&[u8], Method> {
+    fn parse_version(buf: &[u8]) -> Result<&[u8], Version> {
         let (res, s) = context(
-            "Method",
+            "Version",
             alt((
-                tag_no_case("GET".as_bytes()),
-                tag_no_case("POST".as_bytes()),
+                tag_no_case("HTTP/1.1".as_bytes()),
+                tag_no_case("HTTP/1.0".as_bytes()),
             )),
         )(buf)?;
 
         let s = std::str::from_utf8(s)
             .expect("unable to parse into utf8")
             .to_uppercase();
+        let version = Version::try_from(&s[..]).map_err(|_| failure(buf, "Version"))?;
+
+        Ok((res, version))
+    }
 
-        Ok((res, s[..].into()))
+    fn parse_method(buf: &[u8]) -> Result<&[u8], Method> {
+        let (res, s) = context("Method", take_while1(is_tchar))(buf)?;
+
+        // only contains tchars, so this is plain ascii
+        let s = std::str::from_utf8(s).expect("unable to parse into utf8");
+
+        Ok((res, s.into()))
+    }
+
+    /// Characters allowed in a token (RFC 9110 section 5.6.2).
+    fn is_tchar(c: u8) -> bool {
+        c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
     }
 
     #[cfg(test)]
@@ -296,6 +667,14 @@ mod parsing {
                 ("GeT", Method::Get),
                 ("POST", Method::Post),
                 ("PoST", Method::Post),
+                ("HEAD", Method::Head),
+                ("PUT", Method::Put),
+                ("DELETE", Method::Delete),
+                ("PATCH", Method::Patch),
+                ("OPTIONS", Method::Options),
+                ("TRACE", Method::Trace),
+                ("CONNECT", Method::Connect),
+                ("PROPFIND", Method::Extension("PROPFIND".to_string())),
             ];
 
             for (input, exp) in all {
@@ -306,12 +685,21 @@ mod parsing {
 
         #[test]
         fn parse_version() {
-            let all = [("HTTP/1.1", Version::Http11), ("hTTP/1.1", Version::Http11)];
+            let all = [
+                ("HTTP/1.1", Version::Http11),
+                ("hTTP/1.1", Version::Http11),
+                ("HTTP/1.0", Version::Http10),
+            ];
 
             for (input, exp) in all {
                 let (_, m) = super::parse_version(input.as_bytes()).expect("unable to parse");
                 assert_eq!(m, exp);
             }
+
+            assert_eq!(
+                Err(ParseError::UnsupportedVersion("HTTP/2.0".to_string())),
+                Version::try_from("HTTP/2.0")
+            );
         }
 
         #[test]
@@ -323,7 +711,7 @@ mod parsing {
 
             assert_eq!(method, Method::Get);
             assert_eq!(version, Version::Http11);
-            assert_eq!(uri, "/".into());
+            assert_eq!(uri, "/".parse::<Url>().unwrap());
         }
 
         #[test]
@@ -335,7 +723,25 @@ mod parsing {
 
             assert_eq!(method, Method::Get);
             assert_eq!(version, Version::Http11);
-            assert_eq!(uri, "/something?foo=2".into());
+            assert_eq!(uri, "/something?foo=2".parse::<Url>().unwrap());
+        }
+
+        #[test]
+        fn parse_request_line_invalid_url() {
+            let all = ["GET /%zz HTTP/1.1", "GET /ä HTTP/1.1", "GET foo HTTP/1.1"];
+
+            for input in all {
+                assert!(super::parse_request_line(input.as_bytes()).is_err());
+            }
+        }
+
+        #[test]
+        fn parse_request_line_invalid_method() {
+            let all = ["G(T / HTTP/1.1", " / HTTP/1.1"];
+
+            for input in all {
+                assert!(super::parse_request_line(input.as_bytes()).is_err());
+            }
         }
 
         #[test]
@@ -349,64 +755,360 @@ mod parsing {
             assert_eq!("localhost:4221", localhost);
         }
 
+        #[test]
+        fn parse_header_line_whitespace() {
+            let all = [
+                "Host:localhost:4221",
+                "Host:   localhost:4221  ",
+                "Host:\tlocalhost:4221\t",
+            ];
+
+            for input in all {
+                let (_, (host, localhost)) =
+                    super::parse_header_line(input.as_bytes()).expect("able to parse");
+                assert_eq!("Host", host);
+                assert_eq!("localhost:4221", localhost, "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn parse_header_line_obs_text() {
+            let input = b"X-Name: M\xfcller";
+            let (_, (_, value)) = super::parse_header_line(input).expect("able to parse");
+
+            assert_eq!(&b"M\xfcller"[..], value.as_bytes());
+            assert_eq!(None, value.to_str());
+        }
+
+        #[test]
+        fn parse_header_line_invalid() {
+            let all = [
+                "Host : localhost",
+                "Host\t: localhost",
+                "Ho(st: localhost",
+                ": localhost",
+                "Host: local\x00host",
+            ];
+
+            for input in all {
+                let res = super::parse_header_line(input.as_bytes());
+                assert!(res.is_err(), "{:?}", input);
+            }
+        }
+
         #[test]
         fn parse_header_lines() {
-            let input = "Host: localhost:4221\r\nUser-Agent: foobar/1.2.3\r\nAccept: */*\r\n";
-            let (res, headers) =
-                super::parse_header_lines(input.as_bytes()).expect("able to parse");
+            let input = "GET / HTTP/1.1\r\nHost: localhost:4221\r\nUser-Agent: foobar/1.2.3\r\nAccept: */*\r\n\r\n";
+            let (Request { header, .. }, res) = parse(input.as_bytes()).expect("able to parse");
             assert_eq!(res.len(), 0);
+            let headers = header.headers;
+
+            assert_eq!(Some("localhost:4221"), headers.get_str("host"));
+            assert_eq!(Some("*/*"), headers.get_str("Accept"));
+        }
+
+        #[test]
+        fn parse_repeated_header_lines() {
+            let input = "GET / HTTP/1.0\r\nAccept-Encoding: gzip\r\nCookie: a=1\r\naccept-encoding: br\r\n\r\n";
+            let (Request { header, .. }, _) = parse(input.as_bytes()).expect("able to parse");
+            let headers = header.headers;
 
-            assert_eq!(Some(&"localhost:4221".to_string()), headers.get("host"));
-            assert_eq!(Some(&"*/*".to_string()), headers.get("Accept"));
+            assert_eq!(
+                vec!["gzip", "br"],
+                headers.get_all("accept-encoding").collect::<Vec<_>>()
+            );
+            assert_eq!(
+                vec![
+                    ("Accept-Encoding", &"gzip".into()),
+                    ("Cookie", &"a=1".into()),
+                    ("accept-encoding", &"br".into())
+                ],
+                headers.iter().collect::<Vec<_>>()
+            );
         }
 
         #[test]
         fn parse_full_request_no_body() {
             let input = "POST /files HTTP/1.1\r\nHost: localhost:4221\r\nUser-Agent: curl/8.8.0\r\nAccept: */*\r\n\r\n";
-            let (res, Request { header, body }) = parse(input.as_bytes()).expect("able to parse");
+            let (Request { header, body, .. }, res) =
+                parse(input.as_bytes()).expect("able to parse");
             assert_eq!(res.len(), 0);
 
             assert_eq!(header.method, Method::Post);
             assert_eq!(header.version, Version::Http11);
-            assert_eq!(header.url, "/files".into());
+            assert_eq!(header.url, "/files".parse::<Url>().unwrap());
 
-            assert_eq!(
-                Some(&"localhost:4221".to_string()),
-                header.headers.get("Host")
-            );
+            assert_eq!(Some("localhost:4221"), header.headers.get_str("Host"));
 
             assert_eq!(None, body);
         }
 
         #[test]
         fn parse_full_request() {
-            let input = "GET /user-agent HTTP/1.1\r\nHost: localhost:4221\r\nUser-Agent: foobar/1.2.3\r\nAccept: */*\r\n\r\nSome Body";
-            let (res, Request { header, body }) = parse(input.as_bytes()).expect("able to parse");
+            let input = "GET /user-agent HTTP/1.1\r\nHost: localhost:4221\r\nUser-Agent: foobar/1.2.3\r\nAccept: */*\r\nContent-Length: 9\r\n\r\nSome Body";
+            let (Request { header, body, .. }, res) =
+                parse(input.as_bytes()).expect("able to parse");
             assert_eq!(res.len(), 0);
 
             assert_eq!(Some("Some Body".as_bytes().to_vec()), body);
 
             assert_eq!(header.method, Method::Get);
             assert_eq!(header.version, Version::Http11);
-            assert_eq!(header.url, "/user-agent".into());
+            assert_eq!(header.url, "/user-agent".parse::<Url>().unwrap());
 
-            assert_eq!(
-                Some(&"localhost:4221".to_string()),
-                header.headers.get("Host")
+            assert_eq!(Some("localhost:4221"), header.headers.get_str("Host"));
+        }
+
+        #[test]
+        fn parse_unframed_body() {
+            let input = "GET / HTTP/1.1\r\nHost: localhost:4221\r\n\r\nSome Body";
+            let (Request { body, .. }, res) = parse(input.as_bytes()).expect("able to parse");
+
+            assert_eq!(None, body);
+            assert_eq!("Some Body".as_bytes(), res);
+        }
+
+        #[test]
+        fn parse_pipelined() {
+            let input = concat!(
+                "POST /files/a HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\nabc",
+                "POST /files/b HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nde\r\n0\r\n\r\n",
+                "GET /echo/c HTTP/1.1\r\nHost: a\r\n\r\n",
             );
+
+            let (first, res) = parse(input.as_bytes()).expect("able to parse");
+            let (second, res) = parse(res).expect("able to parse");
+            let (third, res) = parse(res).expect("able to parse");
+
+            assert_eq!(0, res.len());
+            assert_eq!(Some("abc".as_bytes()), first.body.as_deref());
+            assert_eq!(Some("de".as_bytes()), second.body.as_deref());
+            assert_eq!(None, third.body);
+            assert_eq!(vec!["echo", "c"], third.header.url.sections);
+        }
+
+        #[test]
+        fn parse_content_length() {
+            let input = "POST /files HTTP/1.0\r\nContent-Length: 9\r\n\r\nsomething";
+            let (Request { header, body, .. }, _) = parse(input.as_bytes()).expect("able to parse");
+
+            assert_eq!(Some("something".as_bytes()), body.as_deref());
+            assert_eq!(Some(9), header.content_length().expect("valid length"));
+        }
+
+        #[test]
+        fn parse_errors() {
+            let all = [
+                ("GET /\r\n\r\n", ParseError::BadRequestLine),
+                ("G(T / HTTP/1.1\r\n\r\n", ParseError::BadRequestLine),
+                ("GET / HTTP/1.1 \r\n\r\n", ParseError::BadRequestLine),
+                (
+                    "GET / HTTP/2.0\r\n\r\n",
+                    ParseError::UnsupportedVersion("HTTP/2.0".to_string()),
+                ),
+                (
+                    "GET /%zz HTTP/1.1\r\n\r\n",
+                    ParseError::InvalidUrl(UrlError::InvalidEncoding("%zz".to_string())),
+                ),
+                (
+                    "GET / HTTP/1.1\r\nHost : x\r\n\r\n",
+                    ParseError::BadHeader("whitespace before colon"),
+                ),
+                (
+                    "GET / HTTP/1.1\r\nA: b\r\n c\r\n\r\n",
+                    ParseError::BadHeader("obsolete line folding"),
+                ),
+                (
+                    "GET / HTTP/1.1\r\nAb\r\n\r\n",
+                    ParseError::BadHeader("malformed field line"),
+                ),
+                (
+                    "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nx\r\n",
+                    ParseError::InvalidChunkedBody,
+                ),
+                (
+                    "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nabc",
+                    ParseError::Incomplete,
+                ),
+                (
+                    "POST / HTTP/1.1\r\nContent-Length: x\r\n\r\nabc",
+                    ParseError::InvalidContentLength,
+                ),
+            ];
+
+            for (input, exp) in all {
+                let err = crate::request::parse(input.as_bytes()).expect_err("invalid request");
+                assert_eq!(exp, err, "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn invalid_content_length() {
+            let all = [
+                "POST / HTTP/1.1\r\nContent-Length: +5\r\n\r\n",
+                "POST / HTTP/1.1\r\nContent-Length: 5, 6\r\n\r\n",
+                "POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n",
+            ];
+
+            for input in all {
+                let res = parse(input.as_bytes()).map(|(req, _)| req);
+                assert_eq!(Err(ParseError::InvalidContentLength), res, "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn negotiate_encoding() {
+            let all = [
+                ("gzip", Some(Encoding::Gzip)),
+                ("GZIP, deflate", Some(Encoding::Gzip)),
+                ("deflate, gzip", Some(Encoding::Gzip)),
+                ("br", Some(Encoding::Identity)),
+                ("", Some(Encoding::Identity)),
+                ("gzip;q=0", Some(Encoding::Identity)),
+                ("gzip;q=0, deflate", Some(Encoding::Deflate)),
+                ("gzip;q=0.5, deflate;q=0.8", Some(Encoding::Deflate)),
+                ("gzip;q=0.5, identity", Some(Encoding::Identity)),
+                ("*", Some(Encoding::Gzip)),
+                ("*;q=0.1, gzip;q=0", Some(Encoding::Deflate)),
+                ("identity;q=0", None),
+                ("gzip;q=0, identity;q=0", None),
+                ("*;q=0", None),
+                ("*;q=0, deflate", Some(Encoding::Deflate)),
+                ("gzip;q=1.5", Some(Encoding::Identity)),
+                ("gzip;q=0.0001", Some(Encoding::Identity)),
+            ];
+
+            for (input, exp) in all {
+                assert_eq!(exp, Encoding::negotiate(input), "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn host_validation() {
+            let all = [
+                (
+                    "GET / HTTP/1.1\r\nHost: LocalHost:4221\r\n\r\n",
+                    Ok(Some("localhost:4221")),
+                ),
+                ("GET / HTTP/1.0\r\n\r\n", Ok(None)),
+                ("GET / HTTP/1.1\r\nHost: \r\n\r\n", Ok(None)),
+                (
+                    "GET http://localhost/echo/a HTTP/1.1\r\nHost: localhost:80\r\n\r\n",
+                    Ok(Some("localhost")),
+                ),
+                (
+                    "GET http://localhost:4221/ HTTP/1.0\r\n\r\n",
+                    Ok(Some("localhost:4221")),
+                ),
+                (
+                    "CONNECT localhost:443 HTTP/1.1\r\nHost: localhost:443\r\n\r\n",
+                    Ok(Some("localhost:443")),
+                ),
+                (
+                    "GET / HTTP/1.1\r\n\r\n",
+                    Err(ParseError::BadHost("missing host")),
+                ),
+                (
+                    "GET / HTTP/1.1\r\nHost: a\r\nHost: a\r\n\r\n",
+                    Err(ParseError::BadHost("duplicate host")),
+                ),
+                (
+                    "GET / HTTP/1.1\r\nHost: a b\r\n\r\n",
+                    Err(ParseError::BadHost("invalid host")),
+                ),
+                (
+                    "GET http://a/ HTTP/1.1\r\nHost: b\r\n\r\n",
+                    Err(ParseError::BadHost("host doesn't match the request target")),
+                ),
+                (
+                    "GET https://a/ HTTP/1.1\r\nHost: a:80\r\n\r\n",
+                    Err(ParseError::BadHost("host doesn't match the request target")),
+                ),
+            ];
+
+            for (input, exp) in all {
+                let res = crate::request::parse(input.as_bytes())
+                    .and_then(|(req, _)| req.header.authority())
+                    .map(|authority| authority.map(|a| a.text()));
+                assert_eq!(exp.map(|a| a.map(str::to_string)), res, "{:?}", input);
+            }
+        }
+
+        #[test]
+        fn target_form_methods() {
+            let all = [
+                "GET * HTTP/1.1\r\nHost: a\r\n\r\n",
+                "GET a:80 HTTP/1.1\r\nHost: a\r\n\r\n",
+                "CONNECT / HTTP/1.1\r\nHost: a\r\n\r\n",
+                "CONNECT * HTTP/1.1\r\nHost: a\r\n\r\n",
+            ];
+
+            for input in all {
+                let res = crate::request::parse(input.as_bytes()).map(|(req, _)| req);
+                assert_eq!(Err(ParseError::BadRequestLine), res, "{:?}", input);
+            }
+
+            let input = "OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n";
+            let (req, _) = crate::request::parse(input.as_bytes()).expect("able to parse");
+            assert_eq!(TargetForm::Asterisk, req.header.url.form);
+        }
+
+        #[test]
+        fn keep_alive() {
+            let all = [
+                ("GET / HTTP/1.1\r\nHost: a\r\n\r\n", true),
+                (
+                    "GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+                    false,
+                ),
+                (
+                    "GET / HTTP/1.1\r\nHost: a\r\nConnection: Upgrade, Close\r\n\r\n",
+                    false,
+                ),
+                ("GET / HTTP/1.0\r\n\r\n", false),
+                ("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n", true),
+            ];
+
+            for (input, exp) in all {
+                let (Request { header, .. }, _) = parse(input.as_bytes()).expect("able to parse");
+                assert_eq!(exp, header.keep_alive(), "{:?}", input);
+            }
         }
 
         #[test]
         fn parse_post() {
             let input = "POST /files HTTP/1.1\r\nHost: localhost:4221\r\nUser-Agent: curl/8.8.0\r\nAccept: */*\r\nContent-Length: 9\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\nsomething";
-            let (res, Request { header, body }) = parse(input.as_bytes()).expect("able to parse");
+            let (Request { header, body, .. }, res) =
+                parse(input.as_bytes()).expect("able to parse");
 
             assert_eq!(res.len(), 0);
 
             assert_eq!(header.method, Method::Post);
             assert_eq!(header.version, Version::Http11);
-            assert_eq!(header.url, "/files".into());
+            assert_eq!(header.url, "/files".parse::<Url>().unwrap());
             assert_eq!(Some("something".as_bytes()), body.as_deref());
         }
+
+        #[test]
+        fn parse_form() {
+            let input = "POST /files HTTP/1.1\r\nHost: a\r\nContent-Length: 21\r\nContent-Type: application/x-www-form-urlencoded; charset=ISO-8859-1\r\n\r\nname=M%FCller&tag=a+b";
+            let (request, _) = parse(input.as_bytes()).expect("able to parse");
+
+            let form = request.form().expect("valid form");
+            assert_eq!(Some("M\u{fc}ller"), form.get("name"));
+            assert_eq!(Some("a b"), form.get("tag"));
+
+            let input = "POST /files HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nContent-Type: application/x-www-form-urlencoded; charset=shift_jis\r\n\r\na=b";
+            let (request, _) = parse(input.as_bytes()).expect("able to parse");
+            assert_eq!(
+                Err(form::FormError::UnsupportedCharset("shift_jis".to_string())),
+                request.form()
+            );
+
+            let input =
+                "POST /files HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nContent-Type: text/plain\r\n\r\na=b";
+            let (request, _) = parse(input.as_bytes()).expect("able to parse");
+            assert_eq!(Err(form::FormError::NotForm), request.form());
+        }
     }
 }
@@ -0,0 +1,78 @@
+// Chunked transfer coding (RFC 9112 section 7.1)
+//
+// 1a;name=value\r\n    // chunk size in hex, optional extensions
+// <0x1a bytes>\r\n      // chunk data
+// 0\r\n                 // last chunk
+// Expires: never\r\n    // optional trailer fields
+// \r\n
+
+use nom::{
+    bytes::{
+        complete::take_while1,
+        streaming::{tag, take_until},
+    },
+    error::{context, VerboseError},
+    sequence::terminated,
+};
+
+type IResult<T, V> = nom::IResult<T, V, VerboseError<T>>;
+
+/// Parses a chunk size line, extensions included, returning the size.
+pub(super) fn parse_chunk_size_line(buf: &[u8]) -> IResult<&[u8], usize> {
+    let (res, line) = context(
+        "chunk size line",
+        terminated(take_until("\r\n"), tag("\r\n")),
+    )(buf)?;
+    let (ext, size) = context("chunk size", take_while1(|c: u8| c.is_ascii_hexdigit()))(line)
+        .map_err(to_failure)?;
+
+    // extensions are allowed, but we don't know of any to act upon
+    if !ext.is_empty() && !ext.trim_ascii_start().starts_with(b";") {
+        return Err(failure(line, "chunk extension"));
+    }
+
+    let size = std::str::from_utf8(size)
+        .ok()
+        .and_then(|s| usize::from_str_radix(s, 16).ok())
+        .ok_or_else(|| failure(line, "chunk size"))?;
+
+    Ok((res, size))
+}
+
+/// The line was already completely read, so there is no point in asking for
+/// more data.
+fn to_failure(err: nom::Err<VerboseError<&[u8]>>) -> nom::Err<VerboseError<&[u8]>> {
+    match err {
+        nom::Err::Incomplete(_) => nom::Err::Failure(VerboseError { errors: vec![] }),
+        nom::Err::Error(e) | nom::Err::Failure(e) => nom::Err::Failure(e),
+    }
+}
+
+fn failure<'a>(input: &'a [u8], ctx: &'static str) -> nom::Err<VerboseError<&'a [u8]>> {
+    nom::Err::Failure(VerboseError {
+        errors: vec![(input, nom::error::VerboseErrorKind::Context(ctx))],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn chunk_size_lines() {
+        let all = [
+            ("4\r\n", Some(4)),
+            ("1a;foo=bar\r\n", Some(26)),
+            ("0 ;last\r\n", Some(0)),
+            ("x\r\n", None),
+            ("4 x\r\n", None),
+            ("\r\n", None),
+        ];
+
+        for (input, exp) in all {
+            let size = parse_chunk_size_line(input.as_bytes()).ok().map(|(_, s)| s);
+            assert_eq!(exp, size, "{:?}", input);
+        }
+    }
+}
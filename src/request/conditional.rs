@@ -0,0 +1,323 @@
+// Conditional requests (RFC 9110 section 13)
+//
+// If-None-Match: "5d-1a2b", W/"old"\r\n     // entity tags, or `*`
+// If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n
+// If-Range: "5d-1a2b"\r\n                   // entity tag or date
+
+use std::time::SystemTime;
+
+use crate::date::parse_http_date;
+
+use super::{Header, Method};
+
+/// entity-tag = [ "W/" ] DQUOTE *etagc DQUOTE
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EntityTag {
+    pub weak: bool,
+    /// The opaque tag without its quotes.
+    pub tag: String,
+}
+
+impl EntityTag {
+    pub fn strong(tag: &str) -> Self {
+        Self {
+            weak: false,
+            tag: tag.to_string(),
+        }
+    }
+
+    /// Both are strong and have the same tag.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Both have the same tag, weak or not.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+
+    pub fn text(&self) -> String {
+        match self.weak {
+            true => format!("W/\"{}\"", self.tag),
+            false => format!("\"{}\"", self.tag),
+        }
+    }
+}
+
+/// The validators of the selected representation, as sent with a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<EntityTag>,
+    /// Only has a precision of seconds, like the dates sent by clients.
+    pub last_modified: Option<SystemTime>,
+}
+
+/// The outcome of evaluating the preconditions of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// The request is to be handled as if there were no preconditions.
+    Proceed,
+    /// 304, the client already has the current representation.
+    NotModified,
+    /// 412
+    Failed,
+}
+
+/// An If-Match or If-None-Match field, `None` for `*` which matches any
+/// current representation.
+fn parse_tags(value: &str) -> Option<Option<Vec<EntityTag>>> {
+    if value.trim() == "*" {
+        return Some(None);
+    }
+
+    let mut tags = vec![];
+    let mut rest = value;
+
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        if rest.is_empty() {
+            return Some(Some(tags));
+        }
+
+        let (weak, quoted) = match rest.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, rest),
+        };
+
+        // etagc excludes DQUOTE, so the tag ends at the next one
+        let quoted = quoted.strip_prefix('"')?;
+        let end = quoted.find('"')?;
+        tags.push(EntityTag {
+            weak,
+            tag: quoted[..end].to_string(),
+        });
+
+        rest = &quoted[end + 1..];
+        if !rest.is_empty() && !rest.starts_with([' ', '\t', ',']) {
+            return None;
+        }
+    }
+}
+
+/// Evaluates the preconditions in the order given by RFC 9110 section 13.2.2,
+/// fields that can't be parsed are ignored.
+///
+/// `current` holds the validators of the current representation, `None` if
+/// there is none, e.g. for a PUT creating a file.
+pub fn evaluate(header: &Header, current: Option<&Validators>) -> Precondition {
+    let tags = |name: &str| header.headers.get_joined(name).and_then(|v| parse_tags(&v));
+    let date = |name: &str| header.headers.get_str(name).and_then(parse_http_date);
+    let is_get_or_head = matches!(header.method, Method::Get | Method::Head);
+
+    let etag = current.and_then(|c| c.etag.as_ref());
+    let last_modified = current.and_then(|c| c.last_modified);
+
+    if let Some(tags) = tags("if-match") {
+        let matches = match (tags, etag) {
+            // `*` only matches if there is a current representation at all
+            (None, _) => current.is_some(),
+            (Some(tags), Some(etag)) => tags.iter().any(|t| t.strong_eq(etag)),
+            (Some(_), None) => false,
+        };
+        if !matches {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = date("if-unmodified-since") {
+        if last_modified.is_some_and(|m| m > since) {
+            return Precondition::Failed;
+        }
+    }
+
+    if let Some(tags) = tags("if-none-match") {
+        let matches = match (tags, etag) {
+            (None, _) => current.is_some(),
+            (Some(tags), Some(etag)) => tags.iter().any(|t| t.weak_eq(etag)),
+            (Some(_), None) => false,
+        };
+        if matches {
+            return match is_get_or_head {
+                true => Precondition::NotModified,
+                false => Precondition::Failed,
+            };
+        }
+    } else if let Some(since) = date("if-modified-since").filter(|_| is_get_or_head) {
+        if last_modified.is_some_and(|m| m <= since) {
+            return Precondition::NotModified;
+        }
+    }
+
+    Precondition::Proceed
+}
+
+/// Whether a Range is to be honoured given the If-Range field, which is the
+/// case if there is none or the representation didn't change.
+pub fn if_range(header: &Header, validators: &Validators) -> bool {
+    let Some(value) = header.headers.get_str("if-range") else {
+        return !header.headers.contains("if-range");
+    };
+
+    // an entity tag has to match strongly, a date exactly
+    if value.starts_with('"') || value.starts_with("W/") {
+        match (parse_tags(value), &validators.etag) {
+            (Some(Some(tags)), Some(etag)) if tags.len() == 1 => tags[0].strong_eq(etag),
+            _ => false,
+        }
+    } else {
+        match (parse_http_date(value), validators.last_modified) {
+            (Some(date), Some(modified)) => date == modified,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::request::parse;
+    use pretty_assertions::assert_eq;
+
+    fn validators() -> Validators {
+        Validators {
+            etag: Some(EntityTag::strong("abc")),
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(784111777)),
+        }
+    }
+
+    fn header(method: &str, field: &str) -> Header {
+        let input = format!(
+            "{} /files/a HTTP/1.1\r\nHost: a\r\n{}\r\n\r\n",
+            method, field
+        );
+        parse(input.as_bytes()).expect("able to parse").0.header
+    }
+
+    #[test]
+    fn parse_entity_tags() {
+        assert_eq!(Some(None), parse_tags(" * "));
+        assert_eq!(
+            Some(Some(vec![
+                EntityTag::strong("a,b"),
+                EntityTag {
+                    weak: true,
+                    tag: "c".to_string()
+                },
+                EntityTag::strong(""),
+            ])),
+            parse_tags("\"a,b\", W/\"c\",\"\"")
+        );
+
+        for input in ["abc", "\"abc", "\"a\"b", "w/\"a\""] {
+            assert_eq!(None, parse_tags(input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn evaluate_preconditions() {
+        let all = [
+            ("GET", "If-None-Match: \"abc\"", Precondition::NotModified),
+            (
+                "HEAD",
+                "If-None-Match: W/\"abc\"",
+                Precondition::NotModified,
+            ),
+            ("GET", "If-None-Match: *", Precondition::NotModified),
+            ("GET", "If-None-Match: \"xyz\"", Precondition::Proceed),
+            ("POST", "If-None-Match: \"abc\"", Precondition::Failed),
+            ("GET", "If-Match: \"abc\"", Precondition::Proceed),
+            ("GET", "If-Match: W/\"abc\"", Precondition::Failed),
+            ("GET", "If-Match: \"xyz\", \"abd\"", Precondition::Failed),
+            ("GET", "If-Match: \"xyz\", *", Precondition::Proceed),
+            ("GET", "If-Match: *", Precondition::Proceed),
+            (
+                "GET",
+                "If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT",
+                Precondition::NotModified,
+            ),
+            (
+                "GET",
+                "If-Modified-Since: Sun, 06 Nov 1994 08:49:36 GMT",
+                Precondition::Proceed,
+            ),
+            (
+                "POST",
+                "If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT",
+                Precondition::Proceed,
+            ),
+            ("GET", "If-Modified-Since: yesterday", Precondition::Proceed),
+            (
+                "GET",
+                "If-Unmodified-Since: Sun, 06 Nov 1994 08:49:36 GMT",
+                Precondition::Failed,
+            ),
+            (
+                "GET",
+                "If-Unmodified-Since: Sun, 06 Nov 1994 08:49:37 GMT",
+                Precondition::Proceed,
+            ),
+            (
+                "GET",
+                "If-None-Match: \"xyz\"\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT",
+                Precondition::Proceed,
+            ),
+            (
+                "GET",
+                "If-Match: \"abc\"\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:36 GMT",
+                Precondition::Proceed,
+            ),
+        ];
+
+        for (method, field, exp) in all {
+            let header = header(method, field);
+            assert_eq!(
+                exp,
+                evaluate(&header, Some(&validators())),
+                "{} {:?}",
+                method,
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_without_representation() {
+        let all = [
+            ("PUT", "If-None-Match: *", Precondition::Proceed),
+            ("PUT", "If-Match: *", Precondition::Failed),
+            ("PUT", "If-Match: \"abc\"", Precondition::Failed),
+            ("DELETE", "If-None-Match: \"abc\"", Precondition::Proceed),
+            (
+                "PUT",
+                "If-Unmodified-Since: Sun, 06 Nov 1994 08:49:37 GMT",
+                Precondition::Proceed,
+            ),
+        ];
+
+        for (method, field, exp) in all {
+            let header = header(method, field);
+            assert_eq!(exp, evaluate(&header, None), "{} {:?}", method, field);
+        }
+
+        let header = header("PUT", "If-None-Match: *");
+        assert_eq!(Precondition::Failed, evaluate(&header, Some(&validators())));
+    }
+
+    #[test]
+    fn evaluate_if_range() {
+        let all = [
+            ("X-Other: 1", true),
+            ("If-Range: \"abc\"", true),
+            ("If-Range: W/\"abc\"", false),
+            ("If-Range: \"xyz\"", false),
+            ("If-Range: Sun, 06 Nov 1994 08:49:37 GMT", true),
+            ("If-Range: Sun, 06 Nov 1994 08:49:38 GMT", false),
+            ("If-Range: garbage", false),
+        ];
+
+        for (field, exp) in all {
+            let header = header("GET", field);
+            assert_eq!(exp, if_range(&header, &validators()), "{:?}", field);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// Cookies sent by the client (RFC 6265 section 5.4)
+//
+// Cookie: session=abc; theme="dark"\r\n
+// Cookie: lang=en\r\n                    // repeated fields are combined
+
+use super::{HeaderMap, HeaderValue};
+
+/// The cookies of a request in the order they were sent, collected from all
+/// `Cookie` fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar<'a> {
+    pairs: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> CookieJar<'a> {
+    /// Collects the cookies, pairs that aren't `name=value` are skipped
+    /// rather than failing the whole request.
+    pub fn parse(headers: &'a HeaderMap) -> Self {
+        let mut pairs = vec![];
+
+        for field in headers.get_all("cookie").filter_map(HeaderValue::to_str) {
+            for pair in field.split(';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+
+                let name = name.trim();
+                if name.is_empty() {
+                    continue;
+                }
+
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+
+                pairs.push((name, value));
+            }
+        }
+
+        Self { pairs }
+    }
+
+    /// The value of the first cookie called `name`.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.pairs.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+    }
+
+    /// The values of all cookies called `name`, which happens if they were
+    /// set for different paths or domains.
+    pub fn get_all<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'a str> + 'b {
+        self.pairs
+            .iter()
+            .filter(move |(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.pairs.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_cookies() {
+        let mut headers = HeaderMap::new();
+        headers.append("Cookie", "session=abc; theme=\"dark\";invalid; =x");
+        headers.append("Host", "localhost");
+        headers.append("cookie", "lang=en; session=def");
+
+        let jar = CookieJar::parse(&headers);
+
+        assert_eq!(
+            vec![
+                ("session", "abc"),
+                ("theme", "dark"),
+                ("lang", "en"),
+                ("session", "def")
+            ],
+            jar.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some("abc"), jar.get("session"));
+        assert_eq!(
+            vec!["abc", "def"],
+            jar.get_all("session").collect::<Vec<_>>()
+        );
+        assert_eq!(None, jar.get("invalid"));
+    }
+
+    #[test]
+    fn parse_no_cookies() {
+        let headers = HeaderMap::new();
+        assert!(CookieJar::parse(&headers).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+// Form bodies (application/x-www-form-urlencoded)
+//
+// Content-Type: application/x-www-form-urlencoded; charset=iso-8859-1\r\n
+//
+// name=M%FCller&tag=a+b   // same syntax as the query string
+
+use super::{url::UrlError, Query};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FormError {
+    #[error("the body is not application/x-www-form-urlencoded")]
+    NotForm,
+    #[error("unsupported charset {0:?}")]
+    UnsupportedCharset(String),
+    #[error("the body contains characters that have to be percent-encoded")]
+    InvalidBody,
+    #[error(transparent)]
+    InvalidEncoding(#[from] UrlError),
+}
+
+/// The charsets percent-encoded octets of a form can be decoded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Latin1,
+}
+
+impl Charset {
+    /// Looks up a `charset` parameter, labels are case-insensitive.
+    pub fn from_label(label: &str) -> Option<Self> {
+        match &label.to_lowercase()[..] {
+            "utf-8" | "utf8" | "us-ascii" => Some(Charset::Utf8),
+            "iso-8859-1" | "iso8859-1" | "latin1" => Some(Charset::Latin1),
+            _ => None,
+        }
+    }
+
+    /// `None` if `bytes` aren't valid in this charset.
+    pub fn decode(&self, bytes: Vec<u8>) -> Option<String> {
+        match self {
+            Charset::Utf8 => String::from_utf8(bytes).ok(),
+            // every octet maps to the code point of the same value
+            Charset::Latin1 => Some(bytes.into_iter().map(char::from).collect()),
+        }
+    }
+}
+
+/// Decodes a form body with the same rules as the query string.
+pub fn parse(body: &[u8], charset: Charset) -> Result<Query, FormError> {
+    // anything outside of ascii has to be percent-encoded, for utf8 we can
+    // still be lenient, for other charsets the octets would be ambiguous
+    let body = std::str::from_utf8(body)
+        .ok()
+        .filter(|body| charset == Charset::Utf8 || body.is_ascii())
+        .ok_or(FormError::InvalidBody)?;
+
+    Ok(Query::parse_with(
+        body.trim_end_matches(['\r', '\n']),
+        charset,
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_charsets() {
+        let form = parse(b"name=M%C3%BCller&tag=a+b", Charset::Utf8).expect("valid form");
+        assert_eq!(
+            vec![("name", "M\u{fc}ller"), ("tag", "a b")],
+            form.iter().collect::<Vec<_>>()
+        );
+
+        let form = parse(b"name=M%FCller", Charset::Latin1).expect("valid form");
+        assert_eq!(Some("M\u{fc}ller"), form.get("name"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse(b"name=M%FCller", Charset::Utf8).is_err());
+        assert!(parse("name=M\u{fc}ller".as_bytes(), Charset::Latin1).is_err());
+        assert!(parse(b"name=\xff", Charset::Utf8).is_err());
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(Some(Charset::Utf8), Charset::from_label("UTF-8"));
+        assert_eq!(Some(Charset::Latin1), Charset::from_label("ISO-8859-1"));
+        assert_eq!(None, Charset::from_label("shift_jis"));
+    }
+}
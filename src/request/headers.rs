@@ -0,0 +1,238 @@
+// Header fields as sent by the client
+//
+// Accept-Encoding: gzip\r\n
+// Cookie: a=1\r\n
+// accept-encoding: br\r\n   // repeated, names are case-insensitive
+
+use std::fmt::Debug;
+
+/// The value of a single header field, kept as bytes as it may contain
+/// obs-text (RFC 9110 section 5.5), which isn't guaranteed to be utf8.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HeaderValue(Vec<u8>);
+
+impl HeaderValue {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The value as text, `None` if it isn't valid utf8.
+    pub fn to_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+}
+
+impl Debug for HeaderValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+impl From<&str> for HeaderValue {
+    fn from(value: &str) -> Self {
+        Self(value.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for HeaderValue {
+    fn from(value: String) -> Self {
+        Self(value.into_bytes())
+    }
+}
+
+impl From<&[u8]> for HeaderValue {
+    fn from(value: &[u8]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
+impl PartialEq<str> for HeaderValue {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for HeaderValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<HeaderValue> for str {
+    fn eq(&self, other: &HeaderValue) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<HeaderValue> for &str {
+    fn eq(&self, other: &HeaderValue) -> bool {
+        other == self
+    }
+}
+
+/// Case-insensitive map of header fields, which keeps every occurrence of a
+/// field together with its original casing in the order it was received.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, HeaderValue)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a field, keeping all previous occurrences of `name`.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<HeaderValue>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// The value of the first occurrence of `name`.
+    pub fn get(&self, name: &str) -> Option<&HeaderValue> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// The value of the first occurrence of `name` as text, `None` if it is
+    /// missing or not valid utf8.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(HeaderValue::to_str)
+    }
+
+    /// The values of all occurrences of `name` in the order they were received.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a HeaderValue> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// All occurrences of `name` combined into a single comma separated value,
+    /// which is equivalent for fields defined as lists (RFC 9110 section 5.3).
+    ///
+    /// List fields are built from tokens, so any obs-text is replaced lossily.
+    pub fn get_joined(&self, name: &str) -> Option<String> {
+        let values: Vec<_> = self
+            .get_all(name)
+            .map(|v| String::from_utf8_lossy(v.as_bytes()))
+            .collect();
+
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(", "))
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// All fields with their original casing in the order they were received.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &HeaderValue)> {
+        self.entries.iter().map(|(k, v)| (&k[..], v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Extend<(String, HeaderValue)> for HeaderMap {
+    fn extend<T: IntoIterator<Item = (String, HeaderValue)>>(&mut self, iter: T) {
+        self.entries.extend(iter);
+    }
+}
+
+impl FromIterator<(String, HeaderValue)> for HeaderMap {
+    fn from_iter<T: IntoIterator<Item = (String, HeaderValue)>>(iter: T) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn headers() -> HeaderMap {
+        [
+            ("Host", "localhost:4221"),
+            ("Accept-Encoding", "gzip"),
+            ("Cookie", "a=1"),
+            ("accept-encoding", "br"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.into()))
+        .collect()
+    }
+
+    #[test]
+    fn get_case_insensitive() {
+        let headers = headers();
+
+        assert_eq!(Some("localhost:4221"), headers.get_str("host"));
+        assert_eq!(Some("localhost:4221"), headers.get_str("HOST"));
+        assert_eq!(Some("gzip"), headers.get_str("accept-encoding"));
+        assert_eq!(None, headers.get("user-agent"));
+        assert!(headers.contains("Cookie"));
+    }
+
+    #[test]
+    fn get_all_occurrences() {
+        let headers = headers();
+
+        assert_eq!(
+            vec!["gzip", "br"],
+            headers
+                .get_all("Accept-Encoding")
+                .filter_map(HeaderValue::to_str)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some("gzip, br".to_string()),
+            headers.get_joined("accept-encoding")
+        );
+        assert_eq!(None, headers.get_joined("user-agent"));
+    }
+
+    #[test]
+    fn keep_order_and_casing() {
+        let mut headers = headers();
+        headers.append("X-Custom", "1");
+
+        assert_eq!(5, headers.len());
+        assert_eq!(
+            vec![
+                "Host",
+                "Accept-Encoding",
+                "Cookie",
+                "accept-encoding",
+                "X-Custom"
+            ],
+            headers.iter().map(|(k, _)| k).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn keep_obs_text() {
+        let mut headers = HeaderMap::new();
+        headers.append("X-Name", "M\u{fc}ller".as_bytes());
+        headers.append("X-Latin1", &b"M\xfcller"[..]);
+
+        assert_eq!(Some("M\u{fc}ller"), headers.get_str("x-name"));
+        assert_eq!(None, headers.get_str("x-latin1"));
+        assert_eq!(
+            Some(&b"M\xfcller"[..]),
+            headers.get("x-latin1").map(HeaderValue::as_bytes)
+        );
+    }
+}
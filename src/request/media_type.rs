@@ -0,0 +1,220 @@
+// Media types and their parameters (RFC 9110 section 8.3.1)
+//
+// Content-Type: text/plain;charset=utf-8\r\n
+// Content-Type: multipart/form-data; boundary="--abc"\r\n
+// Accept: text/html, application/*;q=0.5\r\n
+
+/// A parsed media type, the type and subtype are case-insensitive and
+/// therefore kept in lowercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    essence: String,
+    params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// media-type = type "/" subtype parameters, `None` if `value` doesn't
+    /// match it.
+    pub fn parse(value: &str) -> Option<Self> {
+        let end = value.find(';').unwrap_or(value.len());
+        let (kind, subtype) = value[..end].trim().split_once('/')?;
+
+        let is_token = |s: &str| !s.is_empty() && s.bytes().all(is_tchar);
+        if !is_token(kind) || !is_token(subtype) {
+            return None;
+        }
+
+        Some(Self {
+            essence: format!("{}/{}", kind, subtype).to_lowercase(),
+            params: parse_params(&value[end..])?,
+        })
+    }
+
+    /// The type and subtype without any parameters, e.g. `text/plain`.
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// The value of the first parameter called `name`, parameter names are
+    /// case-insensitive.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| &v[..])
+    }
+}
+
+/// Picks the media type to send given the `Accept` field (RFC 9110 section
+/// 12.5.1), `None` if none of `offers` is acceptable.
+///
+/// Each offer is weighted by the most specific range matching it, offers the
+/// client rates equally are preferred in the given order.
+pub fn negotiate<'a>(accept: &str, offers: &[&'a str]) -> Option<&'a str> {
+    let mut ranges = vec![];
+
+    for range in accept.split(',') {
+        if range.trim().is_empty() {
+            continue;
+        }
+        // elements that are malformed or have an invalid weight are ignored
+        let Some(media) = MediaType::parse(range) else {
+            continue;
+        };
+        let Some(weight) = media.param("q").map_or(Some(1000), super::parse_qvalue) else {
+            continue;
+        };
+        ranges.push((media.essence, weight));
+    }
+
+    let weight = |offer: &str| {
+        let (kind, _) = offer.split_once('/').unwrap_or((offer, ""));
+        let specificity = |range: &str| match range.split_once('/') {
+            _ if range == offer => Some(3),
+            Some((k, "*")) if k == kind => Some(2),
+            Some(("*", "*")) => Some(1),
+            _ => None,
+        };
+
+        ranges
+            .iter()
+            .filter_map(|(range, w)| specificity(range).map(|s| (s, *w)))
+            .max_by_key(|(s, _)| *s)
+            .map_or(0, |(_, w)| w)
+    };
+
+    offers
+        .iter()
+        .map(|offer| (*offer, weight(offer)))
+        .filter(|(_, w)| *w > 0)
+        .fold(None, |best: Option<(&str, u16)>, (offer, w)| match best {
+            Some((_, bw)) if bw >= w => best,
+            _ => Some((offer, w)),
+        })
+        .map(|(offer, _)| offer)
+}
+
+/// parameters = *( OWS ";" OWS [ parameter ] ) with
+/// parameter = token "=" ( token / quoted-string ), quoted values are
+/// returned unescaped.
+///
+/// Also used for other fields sharing the syntax, like Content-Disposition.
+pub(super) fn parse_params(mut rest: &str) -> Option<Vec<(String, String)>> {
+    let mut params = vec![];
+
+    loop {
+        rest = rest.trim_start_matches(is_ows);
+        if rest.is_empty() {
+            return Some(params);
+        }
+
+        rest = rest.strip_prefix(';')?.trim_start_matches(is_ows);
+        // empty parameters are allowed, e.g. a trailing `;`
+        if rest.is_empty() || rest.starts_with(';') {
+            continue;
+        }
+
+        let (name, value) = rest.split_once('=')?;
+        if name.is_empty() || !name.bytes().all(is_tchar) {
+            return None;
+        }
+
+        let (value, res) = match value.strip_prefix('"') {
+            Some(quoted) => parse_quoted(quoted)?,
+            None => {
+                let end = value
+                    .bytes()
+                    .position(|c| !is_tchar(c))
+                    .unwrap_or(value.len());
+                (value[..end].to_string(), &value[end..])
+            }
+        };
+
+        params.push((name.to_lowercase(), value));
+        rest = res;
+    }
+}
+
+/// Reads a quoted-string after its opening DQUOTE, returns the unescaped
+/// value and everything after the closing DQUOTE.
+fn parse_quoted(value: &str) -> Option<(String, &str)> {
+    let mut unescaped = String::new();
+    let mut chars = value.char_indices();
+
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '"' => return Some((unescaped, &value[pos + 1..])),
+            '\\' => unescaped.push(chars.next()?.1),
+            c => unescaped.push(c),
+        }
+    }
+
+    // missing the closing DQUOTE
+    None
+}
+
+fn is_ows(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_media_types() {
+        let media = MediaType::parse("Multipart/Form-Data; boundary=\"a \\\"b\\\"\"; X=1")
+            .expect("valid media type");
+
+        assert_eq!("multipart/form-data", media.essence());
+        assert_eq!(Some("a \"b\""), media.param("boundary"));
+        assert_eq!(Some("1"), media.param("x"));
+        assert_eq!(None, media.param("charset"));
+
+        let media = MediaType::parse("text/plain;charset=utf-8;").expect("valid media type");
+        assert_eq!("text/plain", media.essence());
+        assert_eq!(Some("utf-8"), media.param("Charset"));
+    }
+
+    #[test]
+    fn negotiate_media_types() {
+        let offers = ["text/html", "application/json"];
+        let all = [
+            ("*/*", Some("text/html")),
+            ("application/json", Some("application/json")),
+            ("text/html;q=0.5, application/*", Some("application/json")),
+            ("application/*;q=0.2, */*;q=0.1", Some("application/json")),
+            ("*/*, text/html;q=0", Some("application/json")),
+            ("Text/HTML;level=1", Some("text/html")),
+            ("image/png", None),
+            ("*/*;q=0", None),
+            ("application/json;q=2, text/html;q=0.1", Some("text/html")),
+        ];
+
+        for (accept, exp) in all {
+            assert_eq!(exp, negotiate(accept, &offers), "{:?}", accept);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_media_types() {
+        let all = [
+            "",
+            "text",
+            "text/",
+            "text/plain charset=utf-8",
+            "text/plain; charset",
+            "text/plain; charset=\"utf-8",
+            "te xt/plain",
+        ];
+
+        for value in all {
+            assert_eq!(None, MediaType::parse(value), "{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// Multipart form data (RFC 7578, RFC 2046 section 5.1)
+//
+// preamble, ignored\r\n
+// --boundary\r\n
+// Content-Disposition: form-data; name="file"; filename="a.txt"\r\n
+// Content-Type: text/plain\r\n
+// \r\n
+// <data>\r\n
+// --boundary--\r\n
+// epilogue, ignored
+
+use super::{media_type::parse_params, parsing::parse_header_line, HeaderMap};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MultipartError {
+    #[error("the body is not multipart/form-data")]
+    NotMultipart,
+    #[error("missing or invalid boundary parameter")]
+    MissingBoundary,
+    #[error("malformed multipart body: {0}")]
+    Malformed(&'static str),
+}
+
+/// A single part of a form, borrowing its content from the request body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part<'a> {
+    pub headers: HeaderMap,
+    /// The name of the form field, from Content-Disposition.
+    pub name: Option<String>,
+    /// The name of the uploaded file as sent by the client, which may be
+    /// anything including a path.
+    pub filename: Option<String>,
+    pub body: &'a [u8],
+}
+
+/// boundary := 0*69<bchars> bcharsnospace
+pub fn is_valid_boundary(boundary: &str) -> bool {
+    let is_bchar = |c: u8| c.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&c);
+
+    (1..=70).contains(&boundary.len()) && boundary.bytes().all(is_bchar) && !boundary.ends_with(' ')
+}
+
+/// Splits `body` into its parts, everything before the first and after the
+/// last boundary is ignored.
+pub fn parse<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<Part<'a>>, MultipartError> {
+    let dash_boundary = format!("--{}", boundary);
+    let delimiter = format!("\r\n{}", dash_boundary);
+
+    let start = if body.starts_with(dash_boundary.as_bytes()) {
+        0
+    } else {
+        find(body, delimiter.as_bytes())
+            .map(|pos| pos + 2)
+            .ok_or(MultipartError::Malformed("missing boundary"))?
+    };
+
+    let mut rest = &body[start + dash_boundary.len()..];
+    let mut parts = vec![];
+
+    loop {
+        // the close delimiter, the epilogue is of no interest
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+
+        // transport-padding may follow the boundary
+        let padding = rest
+            .iter()
+            .take_while(|&&c| c == b' ' || c == b'\t')
+            .count();
+        rest = rest[padding..]
+            .strip_prefix(b"\r\n")
+            .ok_or(MultipartError::Malformed("boundary line"))?;
+
+        let end = find(rest, delimiter.as_bytes())
+            .ok_or(MultipartError::Malformed("unterminated part"))?;
+
+        parts.push(parse_part(&rest[..end])?);
+        rest = &rest[end + delimiter.len()..];
+    }
+}
+
+fn parse_part(part: &[u8]) -> Result<Part<'_>, MultipartError> {
+    let (lines, body) = if let Some(body) = part.strip_prefix(b"\r\n") {
+        (&part[..0], body)
+    } else {
+        let end = find(part, b"\r\n\r\n").ok_or(MultipartError::Malformed("part header"))?;
+        (&part[..end], &part[end + 4..])
+    };
+
+    let mut headers = HeaderMap::new();
+    for line in lines.split(|&c| c == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        match parse_header_line(line) {
+            Ok((&[], (name, value))) => headers.append(name, value),
+            _ => return Err(MultipartError::Malformed("part header")),
+        }
+    }
+
+    let disposition = headers
+        .get_str("content-disposition")
+        .ok_or(MultipartError::Malformed("missing content disposition"))?;
+
+    let end = disposition.find(';').unwrap_or(disposition.len());
+    if !disposition[..end].trim().eq_ignore_ascii_case("form-data") {
+        return Err(MultipartError::Malformed("content disposition"));
+    }
+    let params = parse_params(&disposition[end..])
+        .ok_or(MultipartError::Malformed("content disposition"))?;
+
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    };
+    let name = param("name");
+    let filename = param("filename");
+
+    Ok(Part {
+        headers,
+        name,
+        filename,
+        body,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_parts() {
+        let body = b"preamble\r\n\
+            --abc\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\
+            \r\n\
+            hello\r\n\
+            --abc  \r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            line 1\r\nline 2\r\n\
+            --abc--\r\n\
+            epilogue";
+
+        let parts = parse(body, "abc").expect("valid body");
+
+        assert_eq!(2, parts.len());
+        assert_eq!(Some("title".to_string()), parts[0].name);
+        assert_eq!(None, parts[0].filename);
+        assert_eq!(b"hello", parts[0].body);
+
+        assert_eq!(Some("file".to_string()), parts[1].name);
+        assert_eq!(Some("a.txt".to_string()), parts[1].filename);
+        assert_eq!(Some("text/plain"), parts[1].headers.get_str("content-type"));
+        assert_eq!(b"line 1\r\nline 2", parts[1].body);
+    }
+
+    #[test]
+    fn parse_empty_form() {
+        assert_eq!(Ok(vec![]), parse(b"--abc--\r\n", "abc"));
+    }
+
+    #[test]
+    fn parse_invalid_parts() {
+        let all: [&[u8]; 5] = [
+            b"no boundary at all",
+            b"--abc\r\nContent-Disposition: form-data; name=a\r\n\r\nunterminated",
+            b"--abc\r\nContent-Type: text/plain\r\n\r\nx\r\n--abc--",
+            b"--abc\r\nContent-Disposition: attachment\r\n\r\nx\r\n--abc--",
+            b"--abcd\r\nContent-Disposition: form-data\r\n\r\nx\r\n--abc--",
+        ];
+
+        for body in all {
+            assert!(
+                parse(body, "abc").is_err(),
+                "{:?}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+
+    #[test]
+    fn boundaries() {
+        assert!(is_valid_boundary("----WebKitFormBoundary7MA4YWxkTrZu0gW"));
+        assert!(is_valid_boundary("a b"));
+        assert!(!is_valid_boundary(""));
+        assert!(!is_valid_boundary("a "));
+        assert!(!is_valid_boundary(&"a".repeat(71)));
+    }
+}
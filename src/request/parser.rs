@@ -0,0 +1,554 @@
+// Incremental request parser
+//
+// GET /files/a HTTP/1.1\r\n      -> request line
+// Transfer-Encoding: chunked\r\n -> header lines
+// \r\n
+// 3\r\nabc\r\n0\r\n\r\n          -> body, by Content-Length or chunked
+//
+// The bytes can arrive split at any point, the parser keeps what it has seen
+// so far and continues where it stopped once more bytes are fed.
+
+use super::{
+    chunked::parse_chunk_size_line,
+    parsing::{parse_header_line, parse_request_line, to_parse_error},
+    Encoding, Header, HeaderMap, Limits, Method, ParseError, Request, TargetForm, Url, Version,
+};
+
+/// The outcome of feeding bytes to a `Parser`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Parsed {
+    /// All bytes were consumed, but the request isn't complete yet.
+    NeedMore,
+    /// The request is complete after the given amount of bytes, anything
+    /// after them belongs to the next request.
+    Done(Box<Request>, usize),
+    /// The request is malformed or exceeds the limits, there is no telling
+    /// where the next request would start.
+    Error(ParseError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    RequestLine,
+    HeaderLines,
+    Body { remaining: usize },
+    ChunkSize,
+    ChunkData { remaining: usize },
+    ChunkEnd,
+    Trailers,
+    Done,
+}
+
+/// A state machine reading a single request at a time, every byte is looked
+/// at exactly once, no matter how the request is split up.
+///
+/// It starts over after a request is done, so that pipelined requests can be
+/// read by feeding the remaining bytes again.
+#[derive(Debug, Clone)]
+pub struct Parser {
+    limits: Limits,
+    state: State,
+    /// The line currently being read, until its line break arrives.
+    line: Vec<u8>,
+    request_line: Option<(Method, Url, Version)>,
+    headers: HeaderMap,
+    /// Size of the header lines read so far, including their line breaks.
+    header_bytes: usize,
+    header: Option<Header>,
+    body: Vec<u8>,
+    /// Size of the body as sent, i.e. including the chunked framing.
+    body_bytes: usize,
+    trailers: HeaderMap,
+}
+
+impl Parser {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            state: State::RequestLine,
+            line: vec![],
+            request_line: None,
+            headers: HeaderMap::new(),
+            header_bytes: 0,
+            header: None,
+            body: vec![],
+            body_bytes: 0,
+            trailers: HeaderMap::new(),
+        }
+    }
+
+    /// Continues parsing with the next bytes received from the client.
+    ///
+    /// The parser must not be fed any further after it reported an error.
+    pub fn feed(&mut self, buf: &[u8]) -> Parsed {
+        let mut consumed = 0;
+
+        while consumed < buf.len() && self.state != State::Done {
+            match self.advance(&buf[consumed..]) {
+                Ok(n) => consumed += n,
+                Err(err) => return Parsed::Error(err),
+            }
+        }
+
+        if self.state != State::Done {
+            return Parsed::NeedMore;
+        }
+
+        let limits = self.limits;
+        let parser = std::mem::replace(self, Self::new(limits));
+        Parsed::Done(Box::new(parser.into_request()), consumed)
+    }
+
+    /// The header of the current request, once all header lines were read.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Amount of body bytes received so far for the current request.
+    pub fn body_bytes(&self) -> usize {
+        self.body_bytes
+    }
+
+    /// Consumes as much of `buf` as the current state needs, `buf` is never
+    /// empty.
+    fn advance(&mut self, buf: &[u8]) -> Result<usize, ParseError> {
+        let remaining = match self.state {
+            State::Body { remaining } | State::ChunkData { remaining } => remaining,
+            _ => return self.advance_line(buf),
+        };
+
+        let n = remaining.min(buf.len());
+        self.body.extend_from_slice(&buf[..n]);
+        self.count_body(n)?;
+
+        self.state = match (self.state, remaining - n) {
+            (State::Body { .. }, 0) => State::Done,
+            (State::Body { .. }, remaining) => State::Body { remaining },
+            (_, 0) => State::ChunkEnd,
+            (_, remaining) => State::ChunkData { remaining },
+        };
+
+        Ok(n)
+    }
+
+    /// Collects the bytes of a line and handles it once it is complete.
+    fn advance_line(&mut self, buf: &[u8]) -> Result<usize, ParseError> {
+        let (n, complete) = match buf.iter().position(|&c| c == b'\n') {
+            Some(pos) => (pos + 1, true),
+            None => (buf.len(), false),
+        };
+        self.line.extend_from_slice(&buf[..n]);
+
+        match self.state {
+            State::RequestLine => {
+                // the limit is on the line without its line break
+                let len = match complete {
+                    true => self.line.len().saturating_sub(2),
+                    false => self.line.strip_suffix(b"\r").unwrap_or(&self.line).len(),
+                };
+                if len > self.limits.max_request_line {
+                    return Err(ParseError::UriTooLong);
+                }
+            }
+            // the empty line ending the header section doesn't count
+            State::HeaderLines
+                if self.header_bytes + self.line.len() > self.limits.max_header_bytes + 2 =>
+            {
+                return Err(ParseError::HeadersTooLarge);
+            }
+            State::ChunkSize | State::ChunkEnd | State::Trailers => self.count_body(n)?,
+            _ => {}
+        }
+
+        if complete {
+            let line = std::mem::take(&mut self.line);
+            self.handle_line(&line)?;
+        }
+
+        Ok(n)
+    }
+
+    fn handle_line(&mut self, line: &[u8]) -> Result<(), ParseError> {
+        let Some(content) = line.strip_suffix(b"\r\n") else {
+            return Err(match self.state {
+                State::RequestLine => ParseError::BadRequestLine,
+                State::HeaderLines => ParseError::BadHeader("malformed field line"),
+                _ => ParseError::InvalidChunkedBody,
+            });
+        };
+
+        match self.state {
+            // empty lines before the request line should be ignored
+            // (RFC 9112 section 2.2)
+            State::RequestLine if content.is_empty() => {}
+            State::RequestLine => {
+                let (method, url, version) = match parse_request_line(content) {
+                    Ok((&[], request_line)) => request_line,
+                    Ok(_) => return Err(ParseError::BadRequestLine),
+                    Err(err) => return Err(to_parse_error(err)),
+                };
+
+                // authority-form is only used by CONNECT and asterisk-form
+                // only by OPTIONS (RFC 9112 section 3.2)
+                match (&method, url.form) {
+                    (Method::Connect, TargetForm::Authority)
+                    | (Method::Options, TargetForm::Asterisk) => {}
+                    (Method::Connect, _) | (_, TargetForm::Authority | TargetForm::Asterisk) => {
+                        return Err(ParseError::BadRequestLine)
+                    }
+                    _ => {}
+                }
+
+                self.request_line = Some((method, url, version));
+                self.state = State::HeaderLines;
+            }
+            State::HeaderLines if content.is_empty() => self.finish_header()?,
+            State::HeaderLines => {
+                self.header_bytes += line.len();
+
+                // a field line starting with whitespace continues the previous
+                // one, this obsolete line folding has to be rejected
+                if content.first().is_some_and(|&c| c == b' ' || c == b'\t') {
+                    return Err(ParseError::BadHeader("obsolete line folding"));
+                }
+
+                match parse_header_line(content) {
+                    Ok((&[], (name, value))) => self.headers.append(name, value),
+                    Ok(_) => return Err(ParseError::BadHeader("malformed field line")),
+                    Err(err) => return Err(to_parse_error(err)),
+                }
+
+                if self.headers.len() > self.limits.max_headers {
+                    return Err(ParseError::HeadersTooLarge);
+                }
+            }
+            State::ChunkSize => {
+                let size = match parse_chunk_size_line(line) {
+                    Ok((_, size)) => size,
+                    Err(_) => return Err(ParseError::InvalidChunkedBody),
+                };
+
+                self.state = match size {
+                    0 => State::Trailers,
+                    remaining => State::ChunkData { remaining },
+                };
+            }
+            State::ChunkEnd if content.is_empty() => self.state = State::ChunkSize,
+            State::ChunkEnd => return Err(ParseError::InvalidChunkedBody),
+            State::Trailers if content.is_empty() => self.state = State::Done,
+            State::Trailers => {
+                // trailer fields are kept apart, they must not change how the
+                // header section was understood (RFC 9112 section 7.1.2)
+                let Ok((&[], (name, value))) = parse_header_line(content) else {
+                    return Err(ParseError::InvalidChunkedBody);
+                };
+                self.trailers.append(name, value);
+            }
+            State::Body { .. } | State::ChunkData { .. } | State::Done => {
+                unreachable!("not reading a line")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds the header once all header lines were read and decides how the
+    /// body is framed.
+    fn finish_header(&mut self) -> Result<(), ParseError> {
+        let (method, url, version) = self
+            .request_line
+            .take()
+            .expect("request line is read before the header lines");
+        let headers = std::mem::take(&mut self.headers);
+
+        let accept_encoding = headers
+            .get_joined("accept-encoding")
+            .and_then(|l| Encoding::negotiate(&l));
+
+        let header = Header {
+            method,
+            url,
+            version,
+            accept_encoding,
+            headers,
+        };
+        // a body with any other final transfer coding can only be delimited
+        // by closing the connection, which a request can't do, and both
+        // framings at once may be read differently by an intermediary
+        // (RFC 9112 section 6.3)
+        if header.headers.contains("transfer-encoding") {
+            if !header.is_chunked() {
+                return Err(ParseError::BadHeader(
+                    "chunked is not the final transfer coding",
+                ));
+            }
+            if header.headers.contains("content-length") {
+                return Err(ParseError::BadHeader(
+                    "transfer-encoding together with content-length",
+                ));
+            }
+        }
+        self.limits.check_header(&header)?;
+        header.authority()?;
+
+        // without any framing there is no body, everything after the header
+        // belongs to the next request
+        self.state = if header.is_chunked() {
+            State::ChunkSize
+        } else {
+            match header.content_length()? {
+                None | Some(0) => State::Done,
+                Some(remaining) => State::Body { remaining },
+            }
+        };
+        self.header = Some(header);
+
+        Ok(())
+    }
+
+    fn count_body(&mut self, n: usize) -> Result<(), ParseError> {
+        self.body_bytes += n;
+
+        if self.body_bytes > self.limits.max_body {
+            return Err(ParseError::BodyTooLarge);
+        }
+        Ok(())
+    }
+
+    fn into_request(self) -> Request {
+        let header = self
+            .header
+            .expect("header is read before the request is done");
+
+        let body = if header.is_chunked() || !self.body.is_empty() {
+            Some(self.body)
+        } else {
+            None
+        };
+
+        Request {
+            header,
+            body,
+            trailers: self.trailers,
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new(Limits::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const PIPELINED: &str = concat!(
+        "POST /files/a HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\nabc",
+        "POST /files/b HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n2;x=y\r\nde\r\n1\r\nf\r\n0\r\nExpires: never\r\n\r\n",
+        "\r\nGET /echo/c HTTP/1.1\r\nHost: a\r\n\r\n",
+    );
+
+    /// Feeds `input` in pieces of `size` bytes, collecting all requests.
+    fn feed_split(input: &[u8], size: usize) -> Vec<Request> {
+        let mut parser = Parser::default();
+        let mut requests = vec![];
+
+        for mut chunk in input.chunks(size) {
+            while !chunk.is_empty() {
+                match parser.feed(chunk) {
+                    Parsed::NeedMore => break,
+                    Parsed::Done(request, consumed) => {
+                        requests.push(*request);
+                        chunk = &chunk[consumed..];
+                    }
+                    Parsed::Error(err) => panic!("unexpected error {:?}", err),
+                }
+            }
+        }
+
+        requests
+    }
+
+    #[test]
+    fn parse_in_pieces() {
+        let all_at_once = feed_split(PIPELINED.as_bytes(), PIPELINED.len());
+        assert_eq!(3, all_at_once.len());
+
+        assert_eq!(Some("abc".as_bytes()), all_at_once[0].body.as_deref());
+        assert_eq!(Some("def".as_bytes()), all_at_once[1].body.as_deref());
+        assert_eq!(Some("never"), all_at_once[1].trailers.get_str("expires"));
+        assert_eq!(None, all_at_once[1].header.headers.get_str("expires"));
+        assert_eq!(None, all_at_once[2].body);
+        assert_eq!(vec!["echo", "c"], all_at_once[2].header.url.sections);
+
+        for size in 1..PIPELINED.len() {
+            assert_eq!(
+                all_at_once,
+                feed_split(PIPELINED.as_bytes(), size),
+                "split every {} bytes",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn chunked_bodies() {
+        let all = [
+            (
+                "4\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\n\r\n",
+                "Wikipedia in\r\n\r\nchunks.",
+            ),
+            ("4;foo=bar\r\nWiki\r\n0;last\r\n\r\n", "Wiki"),
+            ("0\r\n\r\n", ""),
+        ];
+
+        for (body, exp) in all {
+            let input = format!(
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n{}next",
+                body
+            );
+            match Parser::default().feed(input.as_bytes()) {
+                Parsed::Done(request, consumed) => {
+                    assert_eq!(Some(exp.as_bytes()), request.body.as_deref());
+                    assert_eq!(input.len() - "next".len(), consumed);
+                }
+                res => panic!("unexpected {:?} for {:?}", res, body),
+            }
+        }
+    }
+
+    #[test]
+    fn trailers_kept_apart() {
+        let input = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nContent-Length: 5\r\nHost: b\r\n\r\n";
+        let requests = feed_split(input.as_bytes(), input.len());
+        let header = &requests[0].header;
+
+        assert!(header.is_chunked());
+        assert_eq!(Ok(None), header.content_length());
+        assert_eq!(Some("a"), header.headers.get_str("host"));
+        assert_eq!(Some("5"), requests[0].trailers.get_str("content-length"));
+    }
+
+    #[test]
+    fn header_before_body() {
+        let mut parser = Parser::default();
+
+        assert_eq!(
+            Parsed::NeedMore,
+            parser.feed(b"POST /files/a HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\n")
+        );
+        assert_eq!(None, parser.header());
+
+        assert_eq!(Parsed::NeedMore, parser.feed(b"Content-Length: 3\r\n\r\n"));
+        let header = parser.header().expect("header complete");
+        assert_eq!(Some("100-continue"), header.headers.get_str("expect"));
+        assert_eq!(0, parser.body_bytes());
+
+        assert_eq!(Parsed::NeedMore, parser.feed(b"ab"));
+        assert_eq!(2, parser.body_bytes());
+
+        assert!(matches!(parser.feed(b"cGET"), Parsed::Done(_, 1)));
+        assert_eq!(None, parser.header());
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_request_line: 16,
+            max_header_bytes: 24,
+            max_headers: 1,
+            max_body: 4,
+        };
+        let feed = |input: &str| match Parser::new(limits).feed(input.as_bytes()) {
+            Parsed::NeedMore => Ok(None),
+            Parsed::Done(_, consumed) => Ok(Some(consumed)),
+            Parsed::Error(err) => Err(err),
+        };
+
+        assert_eq!(Ok(Some(18)), feed("GET / HTTP/1.0\r\n\r\n"));
+        assert_eq!(Ok(None), feed("GET / HTTP/1.1\r\nHost: a\r\n"));
+        assert_eq!(Ok(None), feed("GET / HTT"));
+        assert_eq!(Err(ParseError::UriTooLong), feed("GET /something HTTP/1.1"));
+        assert_eq!(Err(ParseError::UriTooLong), feed("GET /something HT"));
+        assert_eq!(
+            Err(ParseError::HeadersTooLarge),
+            feed("GET / HTTP/1.1\r\nUser-Agent: something/1.2.3\r\n\r\n")
+        );
+        assert_eq!(
+            Err(ParseError::HeadersTooLarge),
+            feed("GET / HTTP/1.1\r\nUser-Agent: something/1.2.3")
+        );
+        assert_eq!(
+            Err(ParseError::HeadersTooLarge),
+            feed("GET / HTTP/1.1\r\nA: b\r\nC: d\r\n\r\n")
+        );
+        assert_eq!(
+            Err(ParseError::BodyTooLarge),
+            feed("POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n")
+        );
+
+        // the chunked framing counts towards the body size
+        let mut parser = Parser::new(Limits {
+            max_header_bytes: 64,
+            max_headers: 2,
+            ..limits
+        });
+        assert_eq!(
+            Parsed::Error(ParseError::BodyTooLarge),
+            parser.feed(
+                b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let all = [
+            ("GET / HTTP/1.1\n\r\n", ParseError::BadRequestLine),
+            (
+                "GET / HTTP/1.1\r\nHost: a\n\r\n",
+                ParseError::BadHeader("malformed field line"),
+            ),
+            (
+                "GET / HTTP/1.1\r\n Host: a\r\n\r\n",
+                ParseError::BadHeader("obsolete line folding"),
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nab\r\n",
+                ParseError::InvalidChunkedBody,
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip\r\n\r\n",
+                ParseError::BadHeader("chunked is not the final transfer coding"),
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+                ParseError::BadHeader("chunked is not the final transfer coding"),
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n",
+                ParseError::BadHeader("transfer-encoding together with content-length"),
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nA : b\r\n\r\n",
+                ParseError::InvalidChunkedBody,
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nx\r\n",
+                ParseError::InvalidChunkedBody,
+            ),
+            (
+                "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n4 x\r\nWiki\r\n",
+                ParseError::InvalidChunkedBody,
+            ),
+        ];
+
+        for (input, exp) in all {
+            let res = Parser::default().feed(input.as_bytes());
+            assert_eq!(Parsed::Error(exp), res, "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Query string (application/x-www-form-urlencoded)
+//
+// ?download=1&tag=a&tag=b+c&raw
+//  ^ key=value ^ repeated keys ^ key without a value
+
+use super::{
+    form::Charset,
+    url::{percent_decode, UrlError},
+};
+
+/// The decoded key value pairs of a query string, kept in the order they
+/// were sent and including all repetitions of a key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pairs: Vec<(String, String)>,
+}
+
+impl Query {
+    pub fn parse(value: &str) -> Result<Self, UrlError> {
+        Self::parse_with(value, Charset::Utf8)
+    }
+
+    /// Parses the pairs, decoding percent-encoded octets with `charset`.
+    pub fn parse_with(value: &str, charset: Charset) -> Result<Self, UrlError> {
+        let mut pairs = vec![];
+
+        for pair in value.split('&') {
+            if pair.is_empty() {
+                continue;
+            }
+
+            // keys without a value are treated as having an empty one
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            pairs.push((
+                decode_component(key, charset)?,
+                decode_component(value, charset)?,
+            ));
+        }
+
+        Ok(Self { pairs })
+    }
+
+    /// The first value for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &v[..])
+    }
+
+    /// All values for `key` in the order they were sent.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| &v[..])
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (&k[..], &v[..]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// Decodes a single key or value, `+` stands for a space.
+fn decode_component(value: &str, charset: Charset) -> Result<String, UrlError> {
+    let decoded = percent_decode(&value.replace('+', " "))?;
+    charset
+        .decode(decoded)
+        .ok_or_else(|| UrlError::InvalidUtf8(value.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_pairs() {
+        let query = Query::parse("download=1&tag=a&tag=b+c&raw&&name=my%20report%2Btxt")
+            .expect("able to parse");
+
+        assert_eq!(
+            vec![
+                ("download", "1"),
+                ("tag", "a"),
+                ("tag", "b c"),
+                ("raw", ""),
+                ("name", "my report+txt"),
+            ],
+            query.iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(Some("1"), query.get("download"));
+        assert_eq!(Some("a"), query.get("tag"));
+        assert_eq!(vec!["a", "b c"], query.get_all("tag").collect::<Vec<_>>());
+        assert!(query.contains_key("raw"));
+        assert_eq!(None, query.get("missing"));
+    }
+
+    #[test]
+    fn parse_empty() {
+        let query = Query::parse("").expect("able to parse");
+        assert!(query.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Query::parse("a=%zz").is_err());
+        assert!(Query::parse("a=%FF").is_err());
+    }
+}
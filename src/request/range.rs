@@ -0,0 +1,133 @@
+// Byte ranges (RFC 9110 section 14)
+//
+// Range: bytes=0-499, 1000-, -200
+//              ^ first-last ^ from ^ suffix, the last 200 bytes
+
+use std::ops::RangeInclusive;
+
+/// A single range as requested, it only becomes a concrete range once the
+/// length of the representation is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeSpec {
+    Bounded { first: u64, last: u64 },
+    From(u64),
+    Suffix(u64),
+}
+
+impl RangeSpec {
+    /// The bytes this range selects from a representation of `len` bytes,
+    /// `None` if it isn't satisfiable.
+    pub fn resolve(&self, len: u64) -> Option<RangeInclusive<u64>> {
+        match *self {
+            RangeSpec::Bounded { first, last } if first < len => Some(first..=last.min(len - 1)),
+            RangeSpec::From(first) if first < len => Some(first..=len - 1),
+            RangeSpec::Suffix(suffix) if suffix > 0 && len > 0 => {
+                Some(len - suffix.min(len)..=len - 1)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `bytes` range set, `None` for other units or invalid syntax, in
+/// which case the field has to be ignored.
+pub fn parse(value: &str) -> Option<Vec<RangeSpec>> {
+    let (unit, ranges) = value.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let number = |s: &str| {
+        if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        s.parse::<u64>().ok()
+    };
+
+    let mut specs = vec![];
+    for range in ranges.split(',') {
+        let range = range.trim_matches([' ', '\t']);
+        if range.is_empty() {
+            continue;
+        }
+
+        let spec = match range.split_once('-')? {
+            ("", suffix) => RangeSpec::Suffix(number(suffix)?),
+            (first, "") => RangeSpec::From(number(first)?),
+            (first, last) => {
+                let (first, last) = (number(first)?, number(last)?);
+                if first > last {
+                    return None;
+                }
+                RangeSpec::Bounded { first, last }
+            }
+        };
+        specs.push(spec);
+    }
+
+    if specs.is_empty() {
+        None
+    } else {
+        Some(specs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(
+            Some(vec![
+                RangeSpec::Bounded {
+                    first: 0,
+                    last: 499
+                },
+                RangeSpec::From(1000),
+                RangeSpec::Suffix(200),
+            ]),
+            parse("Bytes=0-499, 1000-,, -200")
+        );
+
+        let all = [
+            "items=0-1",
+            "bytes=",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=1",
+            "bytes=-",
+            "bytes=+1-2",
+        ];
+        for input in all {
+            assert_eq!(None, parse(input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn resolve_ranges() {
+        let all = [
+            (RangeSpec::Bounded { first: 0, last: 4 }, 10, Some(0..=4)),
+            (RangeSpec::Bounded { first: 5, last: 99 }, 10, Some(5..=9)),
+            (
+                RangeSpec::Bounded {
+                    first: 10,
+                    last: 12,
+                },
+                10,
+                None,
+            ),
+            (RangeSpec::From(9), 10, Some(9..=9)),
+            (RangeSpec::From(10), 10, None),
+            (RangeSpec::Suffix(3), 10, Some(7..=9)),
+            (RangeSpec::Suffix(30), 10, Some(0..=9)),
+            (RangeSpec::Suffix(0), 10, None),
+            (RangeSpec::Suffix(3), 0, None),
+        ];
+
+        for (spec, len, exp) in all {
+            assert_eq!(exp, spec.resolve(len), "{:?}", spec);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// Request target (RFC 9112 section 3.2)
+//
+// /files/my%20report.txt?download=1             // origin-form
+// ^ path                 ^ query
+// http://localhost:4221/files/a.txt              // absolute-form, sent to proxies
+// localhost:4221                                 // authority-form, only for CONNECT
+// *                                              // asterisk-form, only for OPTIONS
+
+use std::str::FromStr;
+
+use super::query::Query;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UrlError {
+    #[error("the request target has to start with '/'")]
+    NotAbsolutePath,
+    #[error("invalid character {0:?} in the request target")]
+    InvalidCharacter(char),
+    #[error("invalid percent-encoding in {0:?}")]
+    InvalidEncoding(String),
+    #[error("percent-encoded segment {0:?} is not valid utf8")]
+    InvalidUtf8(String),
+    #[error("percent-encoded segment {0:?} contains '/' or NUL")]
+    InvalidSegment(String),
+    #[error("unsupported scheme {0:?}")]
+    UnsupportedScheme(String),
+    #[error("invalid authority {0:?}")]
+    InvalidAuthority(String),
+}
+
+/// The form the request target was sent in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetForm {
+    Origin,
+    Absolute,
+    Authority,
+    Asterisk,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    /// The request target exactly as sent by the client.
+    pub raw: String,
+    pub form: TargetForm,
+    /// The lowercase scheme of an absolute-form target.
+    pub scheme: Option<String>,
+    /// The authority of an absolute-form or authority-form target.
+    pub authority: Option<Authority>,
+    /// Percent-decoded path segments with `.` and `..` resolved and empty
+    /// segments dropped, the root path is represented as `["/"]`.
+    pub sections: Vec<String>,
+    /// The query string without its `?`, only decoded when it is asked for,
+    /// so that a malformed one doesn't get in the way of routes ignoring it.
+    pub query: String,
+}
+
+impl Url {
+    /// The decoded query string.
+    pub fn query(&self) -> Result<Query, UrlError> {
+        Query::parse(&self.query)
+    }
+}
+
+impl FromStr for Url {
+    type Err = UrlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(c) = value.chars().find(|c| !c.is_ascii_graphic()) {
+            return Err(UrlError::InvalidCharacter(c));
+        }
+
+        let (form, scheme, authority, target) = if value.starts_with('/') {
+            (TargetForm::Origin, None, None, value)
+        } else if value == "*" {
+            (TargetForm::Asterisk, None, None, "")
+        } else if let Some((scheme, rest)) = value.split_once("://") {
+            let scheme = scheme.to_lowercase();
+            if scheme != "http" && scheme != "https" {
+                return Err(UrlError::UnsupportedScheme(scheme));
+            }
+
+            let end = rest.find(['/', '?']).unwrap_or(rest.len());
+            let authority: Authority = rest[..end].parse()?;
+
+            // http(s) URIs with an empty host are invalid (RFC 9110 section 4.2.1)
+            if authority.host.is_empty() {
+                return Err(UrlError::InvalidAuthority(rest[..end].to_string()));
+            }
+
+            (
+                TargetForm::Absolute,
+                Some(scheme),
+                Some(authority),
+                &rest[end..],
+            )
+        } else if !value.contains(['/', '?']) && value.contains(':') {
+            let authority: Authority = value.parse()?;
+
+            if authority.host.is_empty() || authority.port.is_none() {
+                return Err(UrlError::InvalidAuthority(value.to_string()));
+            }
+
+            (TargetForm::Authority, None, Some(authority), "")
+        } else {
+            return Err(UrlError::NotAbsolutePath);
+        };
+
+        let (path, query) = match target.split_once('?') {
+            None => (target, ""),
+            Some((path, query)) => (path, query),
+        };
+
+        let mut sections: Vec<String> = vec![];
+        for segment in path.split('/') {
+            let decoded = String::from_utf8(percent_decode(segment)?)
+                .map_err(|_| UrlError::InvalidUtf8(segment.to_string()))?;
+
+            // an encoded separator would turn a segment into a path of its
+            // own, which could be absolute once joined onto a directory
+            if decoded.contains(['/', '\0']) {
+                return Err(UrlError::InvalidSegment(segment.to_string()));
+            }
+            let segment = decoded;
+
+            // dot segments are resolved after decoding, so that an encoded
+            // `%2E%2E` can't be used to sneak past the normalization
+            match &segment[..] {
+                "" | "." => {}
+                ".." => {
+                    sections.pop();
+                }
+                _ => sections.push(segment),
+            }
+        }
+
+        // this is root
+        if sections.is_empty() {
+            sections.push("/".to_string());
+        }
+
+        Ok(Self {
+            raw: value.to_string(),
+            form,
+            scheme,
+            authority,
+            sections,
+            query: query.to_string(),
+        })
+    }
+}
+
+/// authority = host [ ":" port ], without userinfo, which is not allowed in
+/// http(s) URIs (RFC 9110 section 4.2.4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authority {
+    /// The lowercase host, an IP literal keeps its brackets.
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl Authority {
+    /// Whether both name the same origin, given the default port of the
+    /// scheme in use.
+    pub fn matches(&self, other: &Authority, default_port: u16) -> bool {
+        self.host == other.host
+            && self.port.unwrap_or(default_port) == other.port.unwrap_or(default_port)
+    }
+
+    pub fn text(&self) -> String {
+        match self.port {
+            None => self.host.clone(),
+            Some(port) => format!("{}:{}", self.host, port),
+        }
+    }
+}
+
+impl FromStr for Authority {
+    type Err = UrlError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || UrlError::InvalidAuthority(value.to_string());
+
+        let (host, port) = if value.starts_with('[') {
+            let end = value.find(']').ok_or_else(invalid)?;
+            let (host, rest) = value.split_at(end + 1);
+            match rest {
+                "" => (host, None),
+                rest => (host, Some(rest.strip_prefix(':').ok_or_else(invalid)?)),
+            }
+        } else {
+            match value.rsplit_once(':') {
+                None => (value, None),
+                Some((host, port)) => (host, Some(port)),
+            }
+        };
+
+        // reg-name or IPv4address, both only contain these characters
+        let is_host_char = |c: u8| c.is_ascii_alphanumeric() || b"-._~%!$&'()*+,;=".contains(&c);
+        let is_ip_literal = host.starts_with('[')
+            && host[1..host.len() - 1]
+                .bytes()
+                .all(|c| c.is_ascii_hexdigit() || c == b':' || c == b'.');
+        if !is_ip_literal && !host.bytes().all(is_host_char) {
+            return Err(invalid());
+        }
+
+        // an empty port is the same as none at all
+        let port = match port {
+            None | Some("") => None,
+            Some(port) if port.bytes().all(|c| c.is_ascii_digit()) => {
+                Some(port.parse().map_err(|_| invalid())?)
+            }
+            Some(_) => return Err(invalid()),
+        };
+
+        Ok(Self {
+            host: host.to_lowercase(),
+            port,
+        })
+    }
+}
+
+/// Decodes all `%XX` escapes in `value`.
+pub fn percent_decode(value: &str) -> Result<Vec<u8>, UrlError> {
+    let invalid = || UrlError::InvalidEncoding(value.to_string());
+
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            decoded.push(b);
+            continue;
+        }
+
+        let hi = bytes.next().and_then(hex_value).ok_or_else(invalid)?;
+        let lo = bytes.next().and_then(hex_value).ok_or_else(invalid)?;
+        decoded.push(hi << 4 | lo);
+    }
+
+    Ok(decoded)
+}
+
+/// Encodes `value` for use as a single path segment, everything but the
+/// unreserved characters (RFC 3986 section 2.3) is escaped.
+pub fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    encoded
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn decode_and_normalize() {
+        let all = [
+            ("/", vec!["/"]),
+            ("/files/my%20report.txt", vec!["files", "my report.txt"]),
+            ("/files//a/./b/../c", vec!["files", "a", "c"]),
+            ("/files/%2E%2E/%2e%2E/etc", vec!["etc"]),
+            ("/../../", vec!["/"]),
+            ("/echo/%C3%A4", vec!["echo", "ä"]),
+        ];
+
+        for (input, exp) in all {
+            let url: Url = input.parse().expect("able to parse");
+            assert_eq!(exp, url.sections, "{:?}", input);
+            assert_eq!(input, url.raw);
+        }
+    }
+
+    #[test]
+    fn encode_segment() {
+        let value = "a b/c%\u{fc}.txt";
+        let encoded = percent_encode(value);
+
+        assert_eq!("a%20b%2Fc%25%C3%BC.txt", encoded);
+        assert_eq!(
+            value.as_bytes(),
+            percent_decode(&encoded).expect("valid encoding")
+        );
+    }
+
+    #[test]
+    fn split_query() {
+        let url: Url = "/a%20b?foo=%20&bar".parse().expect("able to parse");
+        assert_eq!(vec!["a b"], url.sections);
+        let query = url.query().expect("valid query");
+        assert_eq!(Some(" "), query.get("foo"));
+        assert_eq!(Some(""), query.get("bar"));
+
+        // only an error once the query is needed
+        let url: Url = "/a?x=%FF".parse().expect("able to parse");
+        assert_eq!(vec!["a"], url.sections);
+        assert_eq!(Err(UrlError::InvalidUtf8("%FF".to_string())), url.query());
+    }
+
+    #[test]
+    fn target_forms() {
+        let url: Url = "HTTP://LocalHost:4221/echo/a?b=c"
+            .parse()
+            .expect("able to parse");
+        assert_eq!(TargetForm::Absolute, url.form);
+        assert_eq!(Some("http"), url.scheme.as_deref());
+        assert_eq!(
+            Some(Authority {
+                host: "localhost".to_string(),
+                port: Some(4221)
+            }),
+            url.authority
+        );
+        assert_eq!(vec!["echo", "a"], url.sections);
+        assert_eq!(Some("c"), url.query().unwrap().get("b"));
+
+        let url: Url = "http://[::1]".parse().expect("able to parse");
+        assert_eq!(vec!["/"], url.sections);
+        assert_eq!(Some("[::1]"), url.authority.as_ref().map(|a| &a.host[..]));
+
+        let url: Url = "example.com:443".parse().expect("able to parse");
+        assert_eq!(TargetForm::Authority, url.form);
+        assert_eq!(
+            Some("example.com:443".to_string()),
+            url.authority.map(|a| a.text())
+        );
+
+        let url: Url = "*".parse().expect("able to parse");
+        assert_eq!(TargetForm::Asterisk, url.form);
+        assert_eq!(None, url.authority);
+    }
+
+    #[test]
+    fn parse_authority() {
+        let all = [
+            ("localhost", Some(("localhost", None))),
+            ("LOCALHOST:4221", Some(("localhost", Some(4221)))),
+            ("127.0.0.1:", Some(("127.0.0.1", None))),
+            ("[::1]:80", Some(("[::1]", Some(80)))),
+            ("", Some(("", None))),
+            ("user@localhost", None),
+            ("localhost:99999", None),
+            ("localhost:+80", None),
+            ("[::1", None),
+            ("[::1]80", None),
+        ];
+
+        for (input, exp) in all {
+            let authority = input.parse::<Authority>().ok();
+            assert_eq!(
+                exp,
+                authority.as_ref().map(|a| (&a.host[..], a.port)),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn reject_invalid() {
+        let all = [
+            ("/files/%zz", UrlError::InvalidEncoding("%zz".to_string())),
+            ("/files/%2", UrlError::InvalidEncoding("%2".to_string())),
+            ("/files/%FF", UrlError::InvalidUtf8("%FF".to_string())),
+            (
+                "/files/%2Fetc%2Fpasswd",
+                UrlError::InvalidSegment("%2Fetc%2Fpasswd".to_string()),
+            ),
+            ("/echo/a%2Fb", UrlError::InvalidSegment("a%2Fb".to_string())),
+            ("/files/a%00", UrlError::InvalidSegment("a%00".to_string())),
+            ("files", UrlError::NotAbsolutePath),
+            ("/fi\tles", UrlError::InvalidCharacter('\t')),
+            (
+                "ftp://localhost/a",
+                UrlError::UnsupportedScheme("ftp".to_string()),
+            ),
+            ("http:///a", UrlError::InvalidAuthority("".to_string())),
+            (
+                "localhost:",
+                UrlError::InvalidAuthority("localhost:".to_string()),
+            ),
+        ];
+
+        for (input, exp) in all {
+            assert_eq!(Err(exp), input.parse::<Url>(), "{:?}", input);
+        }
+    }
+}
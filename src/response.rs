@@ -1,21 +1,136 @@
 #![allow(dead_code)]
-use std::{collections::BTreeSet, io::Write};
+pub mod body;
+pub mod cookie;
 
-use libflate::gzip::Encoder;
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    io::{self, ErrorKind, Write},
+    time::SystemTime,
+};
 
-use crate::request::{Encoding, Version};
+use body::Compressor;
+use libflate::{gzip, zlib};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
+use crate::{
+    date::format_http_date,
+    request::{Encoding, EntityTag, Method, Version},
+};
+
+pub use body::Body;
+pub use cookie::SetCookie;
+
+/// A media type as sent in Content-Type, e.g. `text/html; charset=utf-8`.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum ContentType {
-    TextPlain,
-    OctentStream,
+pub struct ContentType {
+    /// The lowercase type and subtype.
+    essence: Cow<'static, str>,
+    params: Vec<(&'static str, String)>,
 }
 
 impl ContentType {
+    pub const TEXT_PLAIN: ContentType = ContentType::from_static("text/plain");
+    pub const TEXT_HTML: ContentType = ContentType::from_static("text/html");
+    pub const JSON: ContentType = ContentType::from_static("application/json");
+    pub const OCTET_STREAM: ContentType = ContentType::from_static("application/octet-stream");
+
+    /// `essence` has to be a lowercase `type/subtype`.
+    pub const fn from_static(essence: &'static str) -> Self {
+        Self {
+            essence: Cow::Borrowed(essence),
+            params: Vec::new(),
+        }
+    }
+
+    /// `None` if `essence` isn't a valid `type/subtype`.
+    pub fn new(essence: &str) -> Option<Self> {
+        let (kind, subtype) = essence.split_once('/')?;
+        if !is_token(kind) || !is_token(subtype) {
+            return None;
+        }
+
+        Some(Self {
+            essence: Cow::Owned(essence.to_lowercase()),
+            params: Vec::new(),
+        })
+    }
+
+    /// The parts of a multi-range response, separated by `boundary`.
+    pub fn multipart_byteranges(boundary: String) -> Self {
+        Self::from_static("multipart/byteranges").with_param("boundary", boundary)
+    }
+
+    pub fn with_charset(self, charset: &str) -> Self {
+        self.with_param("charset", charset.to_string())
+    }
+
+    pub fn with_param(mut self, name: &'static str, value: String) -> Self {
+        self.params.retain(|(n, _)| *n != name);
+        self.params.push((name, value));
+        self
+    }
+
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| &v[..])
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.essence.to_string();
+        for (name, value) in &self.params {
+            if is_token(value) {
+                text.push_str(&format!("; {}={}", name, value));
+            } else {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                text.push_str(&format!("; {}=\"{}\"", name, escaped));
+            }
+        }
+        text
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c))
+}
+
+/// The part of the representation enclosed in a response, `None` for the
+/// range of a 416 response, which only carries the complete length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ContentRange {
+    pub range: Option<(u64, u64)>,
+    pub len: u64,
+}
+
+impl ContentRange {
+    pub fn text(&self) -> String {
+        match self.range {
+            Some((first, last)) => format!("bytes {}-{}/{}", first, last, self.len),
+            None => format!("bytes */{}", self.len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Connection {
+    KeepAlive,
+    Close,
+}
+
+impl Connection {
     pub fn text(&self) -> &'static str {
         match self {
-            ContentType::TextPlain => "text/plain",
-            ContentType::OctentStream => "application/octet-stream",
+            Connection::KeepAlive => "keep-alive",
+            Connection::Close => "close",
         }
     }
 }
@@ -26,64 +141,300 @@ pub enum Headers {
     ContentLength(usize),
     AcceptEncoding(Encoding),
     ContentEncoding(Encoding),
+    /// We only ever support byte ranges.
+    AcceptRanges,
+    ContentRange(ContentRange),
+    ETag(EntityTag),
+    LastModified(SystemTime),
+    Allow(Vec<Method>),
+    Connection(Connection),
+    Vary(&'static str),
+    SetCookie(SetCookie),
 }
 
 impl Headers {
     pub fn text(&self) -> (&'static str, String) {
         match self {
-            Headers::ContentType(ct) => ("Content-Type", ct.text().to_string()),
+            Headers::ContentType(ct) => ("Content-Type", ct.text()),
             Headers::ContentLength(size) => ("Content-Length", format!("{}", size)),
             Headers::AcceptEncoding(enc) => ("Accept-Encoding", enc.text().to_string()),
             Headers::ContentEncoding(enc) => ("Content-Encoding", enc.text().to_string()),
+            Headers::AcceptRanges => ("Accept-Ranges", "bytes".to_string()),
+            Headers::ContentRange(range) => ("Content-Range", range.text()),
+            Headers::ETag(etag) => ("ETag", etag.text()),
+            Headers::LastModified(time) => ("Last-Modified", format_http_date(*time)),
+            Headers::Allow(methods) => (
+                "Allow",
+                methods
+                    .iter()
+                    .map(Method::text)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            Headers::Connection(conn) => ("Connection", conn.text().to_string()),
+            Headers::Vary(field) => ("Vary", field.to_string()),
+            Headers::SetCookie(cookie) => ("Set-Cookie", cookie.text()),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Status {
+    Continue,
     Ok,
     Created,
+    NoContent,
+    PartialContent,
+    NotModified,
+    BadRequest,
     Forbidden,
     NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    Conflict,
+    PreconditionFailed,
+    ContentTooLarge,
+    UriTooLong,
+    UnsupportedMediaType,
+    RangeNotSatisfiable,
+    ExpectationFailed,
+    RequestHeaderFieldsTooLarge,
     InternalServerError,
+    NotImplemented,
+    HttpVersionNotSupported,
 }
 
 impl Status {
     pub fn code(&self) -> &str {
         match self {
+            Status::Continue => "100",
             Status::Ok => "200",
             Status::Created => "201",
+            Status::NoContent => "204",
+            Status::PartialContent => "206",
+            Status::NotModified => "304",
+            Status::BadRequest => "400",
             Status::Forbidden => "403",
             Status::NotFound => "404",
+            Status::MethodNotAllowed => "405",
+            Status::NotAcceptable => "406",
+            Status::Conflict => "409",
+            Status::PreconditionFailed => "412",
+            Status::ContentTooLarge => "413",
+            Status::UriTooLong => "414",
+            Status::UnsupportedMediaType => "415",
+            Status::RangeNotSatisfiable => "416",
+            Status::ExpectationFailed => "417",
+            Status::RequestHeaderFieldsTooLarge => "431",
             Status::InternalServerError => "500",
+            Status::NotImplemented => "501",
+            Status::HttpVersionNotSupported => "505",
         }
     }
 
+    /// Whether a response with this status can have content at all, those
+    /// that can't mustn't be sent with a Content-Length either.
+    pub fn allows_content(&self) -> bool {
+        !matches!(
+            self,
+            Status::Continue | Status::NoContent | Status::NotModified
+        )
+    }
+
     pub fn reason(&self) -> &str {
         match self {
+            Status::Continue => "Continue",
             Status::Ok => "OK",
             Status::Created => "Created",
+            Status::NoContent => "No Content",
+            Status::PartialContent => "Partial Content",
+            Status::NotModified => "Not Modified",
+            Status::BadRequest => "Bad Request",
             Status::Forbidden => "Forbidden",
             Status::NotFound => "Not Found",
+            Status::MethodNotAllowed => "Method Not Allowed",
+            Status::NotAcceptable => "Not Acceptable",
+            Status::Conflict => "Conflict",
+            Status::PreconditionFailed => "Precondition Failed",
+            Status::ContentTooLarge => "Content Too Large",
+            Status::UriTooLong => "URI Too Long",
+            Status::UnsupportedMediaType => "Unsupported Media Type",
+            Status::RangeNotSatisfiable => "Range Not Satisfiable",
+            Status::ExpectationFailed => "Expectation Failed",
+            Status::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
             Status::InternalServerError => "Internal Server Error",
+            Status::NotImplemented => "Not Implemented",
+            Status::HttpVersionNotSupported => "HTTP Version Not Supported",
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// How the end of a streamed body is marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Length(u64),
+    Chunked,
+    /// The connection is closed, HTTP/1.0 has no other way to end a body of
+    /// unknown length.
+    Close,
+}
+
+#[derive(Debug)]
 pub struct Response {
     pub version: Version,
     pub status: Status,
     pub headers: BTreeSet<Headers>,
     pub accept_encoding: Option<Encoding>,
-    pub body: Option<Vec<u8>>,
+    pub body: Option<Body>,
 }
 
 impl Response {
+    /// Writes the response with a body kept in memory, of a streamed body
+    /// only the head is written, which `send` takes care of.
     pub fn write(&self, buf: &mut Vec<u8>) {
         self.handle_response_line(buf);
         self.handle_headers(buf);
-        self.handle_body(buf);
+        self.handle_body(buf, true);
+    }
+
+    /// Writes the response as it would be sent by `write`, but without the
+    /// body, as required for a response to a HEAD request.
+    pub fn write_head(&self, buf: &mut Vec<u8>) {
+        self.handle_response_line(buf);
+        self.handle_headers(buf);
+        self.handle_body(buf, false);
+    }
+
+    /// Sends the response, a streamed body is read and written piece by
+    /// piece, so that only a bounded part of it is ever held in memory and
+    /// reading waits for the client to keep up.
+    ///
+    /// An error while sending the body leaves the response incomplete, the
+    /// connection has to be closed then.
+    pub async fn send<W>(self, writer: &mut W, with_body: bool) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut buf = Vec::with_capacity(4 * 1024);
+
+        let Some((framing, enc)) = self.stream_framing() else {
+            match with_body {
+                true => self.write(&mut buf),
+                false => self.write_head(&mut buf),
+            }
+            return writer.write_all(&buf).await;
+        };
+
+        self.handle_response_line(&mut buf);
+        self.handle_headers(&mut buf);
+        self.handle_stream_head(&mut buf, framing, enc);
+        writer.write_all(&buf).await?;
+
+        let Some(body) = self.body.filter(|_| with_body) else {
+            return Ok(());
+        };
+
+        let mut compressor = match enc {
+            Some(enc) => Compressor::new(enc)?,
+            None => None,
+        };
+        let mut reader = body.into_reader();
+        let mut piece = vec![0; STREAM_PIECE];
+        let mut sent = 0;
+
+        loop {
+            let n = reader.read(&mut piece).await?;
+            if n == 0 {
+                break;
+            }
+            sent += n as u64;
+
+            match &mut compressor {
+                Some(c) => write_piece(writer, &c.compress(&piece[..n])?, framing).await?,
+                None => write_piece(writer, &piece[..n], framing).await?,
+            }
+        }
+
+        if let Framing::Length(len) = framing {
+            if sent != len {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "the body ended early",
+                ));
+            }
+        }
+        if let Some(c) = compressor {
+            write_piece(writer, &c.finish()?, framing).await?;
+        }
+        if framing == Framing::Chunked {
+            writer.write_all(b"0\r\n\r\n").await?;
+        }
+
+        writer.flush().await
+    }
+
+    /// Whether the connection has to be closed to mark the end of the body.
+    pub fn is_close_delimited(&self) -> bool {
+        self.stream_framing()
+            .is_some_and(|(framing, _)| framing == Framing::Close)
+    }
+
+    /// How a streamed body is delimited and the coding it is sent with,
+    /// `None` for bodies in memory.
+    ///
+    /// The length of a compressed stream isn't known until it is compressed,
+    /// so it can't be sent with a Content-Length.
+    fn stream_framing(&self) -> Option<(Framing, Option<Encoding>)> {
+        let body = self
+            .body
+            .as_ref()
+            .filter(|body| !matches!(body, Body::Bytes(_)))?;
+
+        let enc = self.body_encoding();
+        let compressed = enc.is_some_and(|enc| enc != Encoding::Identity);
+
+        let framing = match body.len() {
+            Some(len) if !compressed => Framing::Length(len),
+            _ if self.version == Version::Http11 => Framing::Chunked,
+            _ => Framing::Close,
+        };
+        Some((framing, enc))
+    }
+
+    /// The coding the body is sent with, `None` if the client didn't ask for
+    /// any.
+    fn body_encoding(&self) -> Option<Encoding> {
+        let body = self.body.as_ref()?;
+
+        // nothing to gain from compressing nothing
+        self.accept_encoding.filter(|_| !body.is_empty())
+    }
+
+    /// Writes the fields describing the coding of the body, if there is one.
+    fn handle_encoding(buf: &mut Vec<u8>, enc: Option<Encoding>) {
+        let Some(enc) = enc else {
+            return;
+        };
+
+        // the body depends on what the client accepts, caches need to know
+        let (key, value) = Headers::Vary("Accept-Encoding").text();
+        Self::insert(key, &value, buf);
+
+        if enc != Encoding::Identity {
+            let (key, value) = Headers::ContentEncoding(enc).text();
+            Self::insert(key, &value, buf);
+        }
+    }
+
+    fn handle_stream_head(&self, buf: &mut Vec<u8>, framing: Framing, enc: Option<Encoding>) {
+        Self::handle_encoding(buf, enc);
+
+        match framing {
+            Framing::Length(len) => Self::insert("Content-Length", &len.to_string(), buf),
+            Framing::Chunked => Self::insert("Transfer-Encoding", "chunked", buf),
+            Framing::Close => {}
+        }
+        buf.extend_from_slice(END_LINE.as_bytes());
     }
 
     fn insert(key: &str, value: &str, buf: &mut Vec<u8>) {
@@ -113,35 +464,75 @@ impl Response {
         }
     }
 
-    fn handle_body(&self, buf: &mut Vec<u8>) {
+    fn handle_body(&self, buf: &mut Vec<u8>, with_body: bool) {
         let handle_writing = |buf: &mut Vec<u8>, body: &[u8]| {
             let (key, value) = Headers::ContentLength(body.len()).text();
             Self::insert(key, &value, buf);
             buf.extend_from_slice(END_LINE.as_bytes());
-            buf.extend_from_slice(body);
-        };
-
-        match &self.body {
-            None => buf.extend_from_slice(END_LINE.as_bytes()),
-            Some(body) => match self.accept_encoding {
-                None => handle_writing(buf, body),
-                Some(enc @ Encoding::Gzip) => {
-                    let (key, value) = Headers::ContentEncoding(enc).text();
-                    Self::insert(key, &value, buf);
-
-                    let mut e = Encoder::new(Vec::new()).expect("unable to create encoder");
-                    e.write_all(body)
-                        .expect("able to correctly write compressed body");
-                    let cbody = e.finish().into_result().expect("unable to compress");
-                    handle_writing(buf, &cbody);
+            if with_body {
+                buf.extend_from_slice(body);
+            }
+        };
+
+        let body = match &self.body {
+            None => return buf.extend_from_slice(END_LINE.as_bytes()),
+            Some(Body::Bytes(body)) => body,
+            Some(_) => {
+                if let Some((framing, enc)) = self.stream_framing() {
+                    self.handle_stream_head(buf, framing, enc);
                 }
-            },
-        }
+                return;
+            }
+        };
+
+        let enc = self.body_encoding();
+        Self::handle_encoding(buf, enc);
+
+        let cbody = match enc {
+            None | Some(Encoding::Identity) => return handle_writing(buf, body),
+            Some(Encoding::Gzip) => {
+                let mut e = gzip::Encoder::new(Vec::new()).expect("unable to create encoder");
+                e.write_all(body)
+                    .expect("able to correctly write compressed body");
+                e.finish().into_result().expect("unable to compress")
+            }
+            Some(Encoding::Deflate) => {
+                let mut e = zlib::Encoder::new(Vec::new()).expect("unable to create encoder");
+                e.write_all(body)
+                    .expect("able to correctly write compressed body");
+                e.finish().into_result().expect("unable to compress")
+            }
+        };
+
+        handle_writing(buf, &cbody);
+    }
+}
+
+/// Writes a piece of a streamed body.
+async fn write_piece<W>(writer: &mut W, data: &[u8], framing: Framing) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    // an empty chunk would end the body
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    if framing == Framing::Chunked {
+        let size = format!("{:x}\r\n", data.len());
+        writer.write_all(size.as_bytes()).await?;
+        writer.write_all(data).await?;
+        writer.write_all(END_LINE.as_bytes()).await
+    } else {
+        writer.write_all(data).await
     }
 }
 
 const END_LINE: &str = "\r\n";
 
+/// The most bytes of a streamed body read at once.
+const STREAM_PIECE: usize = 16 * 1024;
+
 #[cfg(test)]
 mod test {
     use crate::request::Version;
@@ -169,7 +560,7 @@ mod test {
     #[test]
     fn test_with_header() {
         let mut headers = BTreeSet::new();
-        headers.insert(Headers::ContentType(ContentType::TextPlain));
+        headers.insert(Headers::ContentType(ContentType::TEXT_PLAIN));
 
         let res = Response {
             version: Version::Http11,
@@ -189,7 +580,7 @@ mod test {
     #[test]
     fn test_with_body() {
         let mut headers = BTreeSet::new();
-        headers.insert(Headers::ContentType(ContentType::TextPlain));
+        headers.insert(Headers::ContentType(ContentType::TEXT_PLAIN));
 
         let res = Response {
             version: Version::Http11,
@@ -200,7 +591,8 @@ mod test {
                     .as_bytes()
                     .iter()
                     .copied()
-                    .collect_vec(),
+                    .collect_vec()
+                    .into(),
             ),
             accept_encoding: None,
         };
@@ -211,4 +603,360 @@ mod test {
 
         assert_eq!(exp, buffer);
     }
+
+    #[test]
+    fn test_http10_keep_alive() {
+        let mut headers = BTreeSet::new();
+        headers.insert(Headers::Connection(Connection::KeepAlive));
+
+        let res = Response {
+            version: Version::Http10,
+            status: Status::Ok,
+            headers,
+            body: None,
+            accept_encoding: None,
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp = "HTTP/1.0 200 OK\r\nConnection: keep-alive\r\n\r\n".as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    #[test]
+    fn test_empty_body() {
+        let res = Response {
+            version: Version::Http11,
+            status: Status::Created,
+            headers: Default::default(),
+            body: Some(vec![].into()),
+            accept_encoding: Some(Encoding::Gzip),
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp = "HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    #[test]
+    fn test_identity_varies() {
+        let res = Response {
+            version: Version::Http11,
+            status: Status::Ok,
+            headers: Default::default(),
+            body: Some("abc".as_bytes().to_vec().into()),
+            accept_encoding: Some(Encoding::Identity),
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp =
+            "HTTP/1.1 200 OK\r\nVary: Accept-Encoding\r\nContent-Length: 3\r\n\r\nabc".as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    #[test]
+    fn test_compressed() {
+        use std::io::Read;
+
+        for enc in [Encoding::Gzip, Encoding::Deflate] {
+            let res = Response {
+                version: Version::Http11,
+                status: Status::Ok,
+                headers: Default::default(),
+                body: Some("Somebody once told me!".as_bytes().to_vec().into()),
+                accept_encoding: Some(enc),
+            };
+            let mut buffer = Vec::new();
+            res.write(&mut buffer);
+
+            let text = String::from_utf8_lossy(&buffer);
+            assert!(text.contains("Vary: Accept-Encoding\r\n"));
+            assert!(text.contains(&format!("Content-Encoding: {}\r\n", enc.text())));
+
+            let pos = buffer.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+            let mut body = String::new();
+            match enc {
+                Encoding::Gzip => gzip::Decoder::new(&buffer[pos..])
+                    .unwrap()
+                    .read_to_string(&mut body),
+                _ => zlib::Decoder::new(&buffer[pos..])
+                    .unwrap()
+                    .read_to_string(&mut body),
+            }
+            .unwrap();
+
+            assert_eq!("Somebody once told me!", body);
+        }
+    }
+
+    #[test]
+    fn test_content_type() {
+        let all = [
+            (ContentType::TEXT_PLAIN, "text/plain"),
+            (
+                ContentType::TEXT_HTML.with_charset("utf-8"),
+                "text/html; charset=utf-8",
+            ),
+            (
+                ContentType::multipart_byteranges("a b\"c".to_string()),
+                "multipart/byteranges; boundary=\"a b\\\"c\"",
+            ),
+            (
+                ContentType::new("Image/SVG+XML").expect("valid type"),
+                "image/svg+xml",
+            ),
+        ];
+        for (content_type, exp) in all {
+            assert_eq!(exp, content_type.text());
+        }
+
+        assert_eq!(None, ContentType::new("text"));
+        assert_eq!(None, ContentType::new("text/plain; charset=utf-8"));
+    }
+
+    #[test]
+    fn test_validators() {
+        let mut headers = BTreeSet::new();
+        headers.insert(Headers::ETag(EntityTag::strong("5d-1a")));
+        headers.insert(Headers::LastModified(
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(784111777),
+        ));
+
+        let res = Response {
+            version: Version::Http11,
+            status: Status::NotModified,
+            headers,
+            body: None,
+            accept_encoding: None,
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp = "HTTP/1.1 304 Not Modified\r\nETag: \"5d-1a\"\r\nLast-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n".as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    #[test]
+    fn test_content_range() {
+        let mut headers = BTreeSet::new();
+        headers.insert(Headers::AcceptRanges);
+        let range = ContentRange {
+            range: Some((0, 3)),
+            len: 10,
+        };
+        headers.insert(Headers::ContentRange(range));
+
+        let res = Response {
+            version: Version::Http11,
+            status: Status::PartialContent,
+            headers,
+            body: Some(b"abcd".to_vec().into()),
+            accept_encoding: None,
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp = "HTTP/1.1 206 Partial Content\r\nAccept-Ranges: bytes\r\nContent-Range: bytes 0-3/10\r\nContent-Length: 4\r\n\r\nabcd".as_bytes();
+
+        assert_eq!(exp, buffer);
+        assert_eq!(
+            "bytes */10",
+            ContentRange {
+                range: None,
+                len: 10
+            }
+            .text()
+        );
+    }
+
+    #[test]
+    fn test_set_cookies() {
+        let mut headers = BTreeSet::new();
+        let session = SetCookie::new("session", "abc").unwrap().http_only();
+        headers.insert(Headers::SetCookie(session));
+        headers.insert(Headers::SetCookie(SetCookie::new("lang", "en").unwrap()));
+
+        let res = Response {
+            version: Version::Http11,
+            status: Status::Ok,
+            headers,
+            body: None,
+            accept_encoding: None,
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp =
+            "HTTP/1.1 200 OK\r\nSet-Cookie: lang=en\r\nSet-Cookie: session=abc; HttpOnly\r\n\r\n"
+                .as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    #[test]
+    fn test_head() {
+        let mut headers = BTreeSet::new();
+        headers.insert(Headers::ContentType(ContentType::TEXT_PLAIN));
+
+        let res = Response {
+            version: Version::Http11,
+            status: Status::Ok,
+            headers,
+            body: Some("Somebody once told me!".as_bytes().to_vec().into()),
+            accept_encoding: None,
+        };
+        let mut buffer = Vec::new();
+        res.write_head(&mut buffer);
+        let exp =
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 22\r\n\r\n".as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    #[test]
+    fn test_allow() {
+        let mut headers = BTreeSet::new();
+        headers.insert(Headers::Allow(vec![Method::Get, Method::Head]));
+
+        let res = Response {
+            version: Version::Http11,
+            status: Status::MethodNotAllowed,
+            headers,
+            body: None,
+            accept_encoding: None,
+        };
+        let mut buffer = Vec::new();
+        res.write(&mut buffer);
+        let exp = "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, HEAD\r\n\r\n".as_bytes();
+
+        assert_eq!(exp, buffer);
+    }
+
+    fn stream(content: &[u8], len: Option<u64>) -> Response {
+        Response {
+            version: Version::Http11,
+            status: Status::Ok,
+            headers: Default::default(),
+            body: Some(Body::stream(io::Cursor::new(content.to_vec()), len)),
+            accept_encoding: None,
+        }
+    }
+
+    /// Splits a response into its head and body.
+    fn split(buffer: &[u8]) -> (String, &[u8]) {
+        let pos = buffer.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        (
+            String::from_utf8_lossy(&buffer[..pos]).into(),
+            &buffer[pos..],
+        )
+    }
+
+    /// Joins the chunks of a chunked body.
+    fn dechunk(mut body: &[u8]) -> Vec<u8> {
+        let mut content = vec![];
+        loop {
+            let pos = body.windows(2).position(|w| w == b"\r\n").unwrap();
+            let size = std::str::from_utf8(&body[..pos]).unwrap();
+            let size = usize::from_str_radix(size, 16).unwrap();
+            body = &body[pos + 2..];
+            if size == 0 {
+                assert_eq!(b"\r\n", body);
+                return content;
+            }
+            content.extend_from_slice(&body[..size]);
+            assert_eq!(b"\r\n", &body[size..size + 2]);
+            body = &body[size + 2..];
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_length() {
+        let content = vec![b'x'; 3 * STREAM_PIECE + 5];
+
+        let mut buffer = Vec::new();
+        let res = stream(&content, Some(content.len() as u64));
+        assert!(!res.is_close_delimited());
+        res.send(&mut buffer, true).await.unwrap();
+        let (head, body) = split(&buffer);
+
+        let exp = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+            content.len()
+        );
+        assert_eq!(exp, head);
+        assert_eq!(content, body);
+
+        // only the head
+        let mut buffer = Vec::new();
+        let res = stream(&content, Some(content.len() as u64));
+        res.send(&mut buffer, false).await.unwrap();
+        assert_eq!(exp.as_bytes(), buffer);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunked() {
+        let content = (0..2 * STREAM_PIECE).map(|i| i as u8).collect_vec();
+
+        let mut buffer = Vec::new();
+        stream(&content, None)
+            .send(&mut buffer, true)
+            .await
+            .unwrap();
+        let (head, body) = split(&buffer);
+
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+            head
+        );
+        assert_eq!(content, dechunk(body));
+    }
+
+    #[tokio::test]
+    async fn test_stream_compressed() {
+        use std::io::Read;
+
+        let content = "Somebody once told me!".repeat(1000);
+
+        // compressing changes the length, so it can't be sent upfront
+        let mut res = stream(content.as_bytes(), Some(content.len() as u64));
+        res.accept_encoding = Some(Encoding::Gzip);
+        let mut buffer = Vec::new();
+        res.send(&mut buffer, true).await.unwrap();
+        let (head, body) = split(&buffer);
+
+        let exp = "HTTP/1.1 200 OK\r\nVary: Accept-Encoding\r\nContent-Encoding: gzip\r\n\
+                   Transfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(exp, head);
+
+        let body = dechunk(body);
+        let mut text = String::new();
+        gzip::Decoder::new(&body[..])
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(content, text);
+    }
+
+    #[tokio::test]
+    async fn test_stream_http10() {
+        let mut res = stream(b"abc", None);
+        res.version = Version::Http10;
+        assert!(res.is_close_delimited());
+
+        let mut buffer = Vec::new();
+        res.send(&mut buffer, true).await.unwrap();
+
+        assert_eq!(b"HTTP/1.0 200 OK\r\n\r\nabc", &buffer[..]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_ended_early() {
+        let mut buffer = Vec::new();
+        let err = stream(b"abc", Some(5))
+            .send(&mut buffer, true)
+            .await
+            .unwrap_err();
+
+        assert_eq!(ErrorKind::UnexpectedEof, err.kind());
+    }
 }
@@ -0,0 +1,139 @@
+// Response bodies
+//
+// Only small bodies are kept in memory, files and other streams are read
+// piece by piece while they are written to the connection.
+
+use std::{fmt::Debug, io, mem, pin::Pin};
+
+use libflate::{gzip, zlib};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt},
+};
+
+use crate::request::Encoding;
+
+pub enum Body {
+    Bytes(Vec<u8>),
+    /// `len` bytes of `file` from its current position on, boxed as a
+    /// handle is rather large.
+    File {
+        file: Box<File>,
+        len: u64,
+    },
+    /// Anything else that can be read, `len` is `None` if it isn't known
+    /// before everything has been read.
+    Stream {
+        reader: Pin<Box<dyn AsyncRead + Send + Sync>>,
+        len: Option<u64>,
+    },
+}
+
+impl Body {
+    pub fn file(file: File, len: u64) -> Self {
+        Body::File {
+            file: Box::new(file),
+            len,
+        }
+    }
+
+    pub fn stream(reader: impl AsyncRead + Send + Sync + 'static, len: Option<u64>) -> Self {
+        Body::Stream {
+            reader: Box::pin(reader),
+            len,
+        }
+    }
+
+    /// The length in bytes, `None` if it isn't known upfront.
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::File { len, .. } => Some(*len),
+            Body::Stream { len, .. } => *len,
+        }
+    }
+
+    /// Whether the body is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Reads at most the length of the body, if it is known.
+    pub(super) fn into_reader(self) -> Pin<Box<dyn AsyncRead + Send + Sync>> {
+        match self {
+            Body::Bytes(bytes) => Box::pin(io::Cursor::new(bytes)),
+            Body::File { file, len } => Box::pin(file.take(len)),
+            Body::Stream {
+                reader,
+                len: Some(len),
+            } => Box::pin(reader.take(len)),
+            Body::Stream { reader, len: None } => reader,
+        }
+    }
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            Body::File { len, .. } => f.debug_struct("File").field("len", len).finish(),
+            Body::Stream { len, .. } => f.debug_struct("Stream").field("len", len).finish(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Bytes(value)
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Body::Bytes(value.into_bytes())
+    }
+}
+
+/// Applies a content coding to a body that is compressed piece by piece.
+pub(super) enum Compressor {
+    Gzip(gzip::Encoder<Vec<u8>>),
+    Deflate(zlib::Encoder<Vec<u8>>),
+}
+
+impl Compressor {
+    /// `None` for the identity coding.
+    pub fn new(enc: Encoding) -> io::Result<Option<Self>> {
+        let compressor = match enc {
+            Encoding::Identity => return Ok(None),
+            Encoding::Gzip => Compressor::Gzip(gzip::Encoder::new(Vec::new())?),
+            Encoding::Deflate => Compressor::Deflate(zlib::Encoder::new(Vec::new())?),
+        };
+        Ok(Some(compressor))
+    }
+
+    /// Compresses `data`, returns whatever compressed output is ready, which
+    /// may well be nothing until enough data was compressed.
+    pub fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        use std::io::Write;
+
+        let out = match self {
+            Compressor::Gzip(e) => {
+                e.write_all(data)?;
+                e.as_inner_mut()
+            }
+            Compressor::Deflate(e) => {
+                e.write_all(data)?;
+                e.as_inner_mut()
+            }
+        };
+        Ok(mem::take(out))
+    }
+
+    /// The rest of the compressed output.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Compressor::Gzip(e) => e.finish().into_result(),
+            Compressor::Deflate(e) => e.finish().into_result(),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+// Cookies set by the server (RFC 6265 section 4.1)
+//
+// Set-Cookie: session=abc; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax\r\n
+
+use std::time::SystemTime;
+
+use crate::date::format_http_date;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CookieError {
+    #[error("invalid cookie name {0:?}")]
+    InvalidName(String),
+    #[error("invalid cookie value {0:?}")]
+    InvalidValue(String),
+    #[error("invalid cookie attribute {0}={1:?}")]
+    BadAttribute(&'static str, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn text(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A single `Set-Cookie` field, every cookie is sent on its own line.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SetCookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<i64>,
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl SetCookie {
+    pub fn new(name: &str, value: &str) -> Result<Self, CookieError> {
+        // cookie-name = token
+        let is_tchar = |c: u8| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c);
+        if name.is_empty() || !name.bytes().all(is_tchar) {
+            return Err(CookieError::InvalidName(name.to_string()));
+        }
+
+        // cookie-octet, no whitespace, quotes, commas, semicolons or backslashes
+        let is_cookie_octet = |c: u8| c.is_ascii_graphic() && !b"\",;\\".contains(&c);
+        if !value.bytes().all(is_cookie_octet) {
+            return Err(CookieError::InvalidValue(value.to_string()));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: None,
+            domain: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        })
+    }
+
+    /// A cookie telling the client to drop the cookie called `name`.
+    pub fn removal(name: &str) -> Result<Self, CookieError> {
+        Ok(Self::new(name, "")?.max_age(0))
+    }
+
+    pub fn path(mut self, path: &str) -> Result<Self, CookieError> {
+        self.path = Some(attribute_value("Path", path)?);
+        Ok(self)
+    }
+
+    pub fn domain(mut self, domain: &str) -> Result<Self, CookieError> {
+        self.domain = Some(attribute_value("Domain", domain)?);
+        Ok(self)
+    }
+
+    /// Lifetime in seconds, zero or less expires the cookie immediately.
+    pub fn max_age(mut self, seconds: i64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn expires(mut self, time: SystemTime) -> Self {
+        self.expires = Some(time);
+        self
+    }
+
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!("{}={}", self.name, self.value);
+
+        if let Some(path) = &self.path {
+            text.push_str(&format!("; Path={}", path));
+        }
+        if let Some(domain) = &self.domain {
+            text.push_str(&format!("; Domain={}", domain));
+        }
+        if let Some(max_age) = self.max_age {
+            text.push_str(&format!("; Max-Age={}", max_age));
+        }
+        if let Some(expires) = self.expires {
+            text.push_str(&format!("; Expires={}", format_http_date(expires)));
+        }
+        if self.secure {
+            text.push_str("; Secure");
+        }
+        if self.http_only {
+            text.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            text.push_str(&format!("; SameSite={}", same_site.text()));
+        }
+
+        text
+    }
+}
+
+/// av-octet, any printable character but `;`, which would start another
+/// attribute.
+fn attribute_value(attribute: &'static str, value: &str) -> Result<String, CookieError> {
+    let is_av_octet = |c: u8| (0x20..0x7f).contains(&c) && c != b';';
+    if !value.bytes().all(is_av_octet) {
+        return Err(CookieError::BadAttribute(attribute, value.to_string()));
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn all_attributes() {
+        let cookie = SetCookie::new("session", "abc")
+            .expect("valid cookie")
+            .path("/")
+            .expect("valid path")
+            .domain("example.com")
+            .expect("valid domain")
+            .max_age(3600)
+            .expires(UNIX_EPOCH + Duration::from_secs(784111777))
+            .secure()
+            .http_only()
+            .same_site(SameSite::Lax);
+
+        assert_eq!(
+            "session=abc; Path=/; Domain=example.com; Max-Age=3600; \
+             Expires=Sun, 06 Nov 1994 08:49:37 GMT; Secure; HttpOnly; SameSite=Lax",
+            cookie.text()
+        );
+    }
+
+    #[test]
+    fn removal() {
+        let cookie = SetCookie::removal("session").expect("valid cookie");
+        assert_eq!("session=; Max-Age=0", cookie.text());
+    }
+
+    #[test]
+    fn invalid() {
+        let all = [
+            ("", "a"),
+            ("a b", "a"),
+            ("a;", "a"),
+            ("a", "a b"),
+            ("a", "a;b"),
+        ];
+
+        for (name, value) in all {
+            assert!(SetCookie::new(name, value).is_err(), "{:?}", (name, value));
+        }
+
+        let cookie = SetCookie::new("a", "b").expect("valid cookie");
+        for path in ["/a;Secure", "/a\r\nX: y", "/\u{e4}"] {
+            assert_eq!(
+                Err(CookieError::BadAttribute("Path", path.to_string())),
+                cookie.clone().path(path),
+                "{:?}",
+                path
+            );
+        }
+        assert_eq!(
+            Err(CookieError::BadAttribute(
+                "Domain",
+                "a.com; Secure".to_string()
+            )),
+            cookie.domain("a.com; Secure")
+        );
+    }
+}